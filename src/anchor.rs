@@ -0,0 +1,231 @@
+//! helpers for executing bundles from anchor programs, enabled by the `anchor`
+//! feature. the accounts of a bundle are passed as the remaining accounts of the
+//! instruction, which the bundle must use exactly, see `handle_anyix_remaining`.
+//!
+//! an anchor program adds an anyix instruction with
+//!
+//! ```ignore
+//! // at the crate root, so that `#[program]` finds the accounts of `AnyIxWithConfig`
+//! pub use anyix::anchor::*;
+//!
+//! #[program]
+//! pub mod example {
+//!     use super::*;
+//!
+//!     pub fn anyix<'info>(
+//!         ctx: Context<'_, '_, '_, 'info, AnyIxWithConfig<'info>>,
+//!         data: Vec<u8>,
+//!     ) -> Result<()> {
+//!         anyix_with_config(&ctx, &data, &anyix::ProgramAllowlist(&[spl_token::ID]))
+//!     }
+//! }
+//! ```
+
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::program_error::ProgramError;
+use anchor_lang::Bumps;
+
+use crate::config::{load_config, CONFIG_SEED};
+use crate::{AnyIxError, AnyIxPolicy, AnyIxRef, Executor};
+
+/// the accounts of an instruction executing a bundle on behalf of one of the
+/// authorities held by an `AnyIxConfig`, followed by the remaining accounts used
+/// by the bundle
+#[derive(Accounts)]
+pub struct AnyIxWithConfig<'info> {
+    /// one of the authorities held by `config`
+    pub authority: Signer<'info>,
+    /// CHECK: the config of the program, at `config_address`. its owner and
+    /// contents, and its address again, are checked by `anyix_with_config`
+    #[account(seeds = [CONFIG_SEED], bump)]
+    pub config: UncheckedAccount<'info>,
+}
+
+/// same as `handle_anyix_remaining`, executing the bundle with the remaining
+/// accounts of `ctx`
+pub fn anyix<'info, T: Bumps>(
+    ctx: &Context<'_, '_, '_, 'info, T>,
+    data: &[u8],
+    policy: &dyn AnyIxPolicy,
+) -> Result<()> {
+    execute(
+        Executor::new(ctx.program_id, policy),
+        ctx.remaining_accounts,
+        data,
+    )
+}
+
+/// same as `handle_anyix_with_config`, but the authority is the `authority`
+/// account of `ctx` rather than a signer of the bundle, and the bundle is
+/// executed with the remaining accounts of `ctx`, which it must use exactly
+pub fn anyix_with_config<'info>(
+    ctx: &Context<'_, '_, '_, 'info, AnyIxWithConfig<'info>>,
+    data: &[u8],
+    policy: &dyn AnyIxPolicy,
+) -> Result<()> {
+    check_config(ctx)?;
+    anyix(ctx, data, policy)
+}
+
+/// same as `anyix_with_config`, but additionally allows the bundle to sign for
+/// PDAs within `signer_namespace`, see `handle_anyix_signed_with_config`
+pub fn anyix_signed_with_config<'info>(
+    ctx: &Context<'_, '_, '_, 'info, AnyIxWithConfig<'info>>,
+    data: &[u8],
+    signer_namespace: &[u8],
+    policy: &dyn AnyIxPolicy,
+) -> Result<()> {
+    check_config(ctx)?;
+    if signer_namespace.is_empty() {
+        return Err(AnyIxErrorCode::SignerNamespace.into());
+    }
+    execute(
+        Executor::new(ctx.program_id, policy).with_signer_namespace(signer_namespace),
+        ctx.remaining_accounts,
+        data,
+    )
+}
+
+/// converts an error returned by this crate into an anchor error, so that anyix
+/// errors are reported by name
+pub fn into_anchor_error(err: ProgramError) -> Error {
+    match err {
+        ProgramError::Custom(code) => match AnyIxError::from_code(code) {
+            Some(err) => AnyIxErrorCode::from(err).into(),
+            None => err.into(),
+        },
+        err => err.into(),
+    }
+}
+
+// checks the authority of `ctx` is one of the authorities of the config
+fn check_config(ctx: &Context<'_, '_, '_, '_, AnyIxWithConfig<'_>>) -> Result<()> {
+    let config = load_config(ctx.program_id, &ctx.accounts.config).map_err(into_anchor_error)?;
+    if !config.is_authorized(&[ctx.accounts.authority.to_account_info()]) {
+        return Err(AnyIxErrorCode::Unauthorized.into());
+    }
+    Ok(())
+}
+
+fn execute(executor: Executor<'_>, accounts: &[AccountInfo<'_>], data: &[u8]) -> Result<()> {
+    let arb_ix = AnyIxRef::unpack(data).map_err(|err| Error::from(AnyIxErrorCode::from(err)))?;
+    executor
+        .with_exact_accounts()
+        .execute(accounts, &arb_ix)
+        .map_err(into_anchor_error)
+}
+
+/// the errors of `AnyIxError`, as an anchor error code. the codes are identical,
+/// so either can be used to decode the error of a failed transaction
+#[error_code(offset = 0x414e_0000)]
+pub enum AnyIxErrorCode {
+    #[msg("anyix header is truncated")]
+    TruncatedHeader,
+    #[msg("anyix instruction data is truncated")]
+    DataUnderflow,
+    #[msg("anyix payload has trailing bytes")]
+    TrailingBytes,
+    #[msg("not enough accounts for anyix bundle")]
+    AccountUnderflow,
+    #[msg("self invocation not allowed")]
+    SelfInvocation,
+    #[msg("unsupported anyix version")]
+    UnsupportedVersion,
+    #[msg("anyix lengths do not match instruction count")]
+    LengthMismatch,
+    #[msg("anyix length overflows encoding")]
+    LengthOverflow,
+    #[msg("invalid anyix extension")]
+    InvalidExtension,
+    #[msg("signed invocation not allowed")]
+    SignerNotAllowed,
+    #[msg("signer seeds do not match instruction accounts")]
+    InvalidSignerSeeds,
+    #[msg("signer seeds outside of permitted namespace")]
+    SignerNamespace,
+    #[msg("program not allowed by anyix policy")]
+    ProgramNotAllowed,
+    #[msg("instruction not allowed by anyix policy")]
+    InstructionNotAllowed,
+    #[msg("signer forwarding not allowed by anyix policy")]
+    SignerForwardingNotAllowed,
+    #[msg("return data not set by the expected program")]
+    ReturnDataMismatch,
+    #[msg("anyix patch out of bounds")]
+    PatchOutOfBounds,
+    #[msg("anyix balance assertion failed")]
+    AssertionFailed,
+    #[msg("anyix assertion account is not a token account")]
+    InvalidTokenAccount,
+    #[msg("anyix precondition failed")]
+    PreconditionFailed,
+    #[msg("invalid anyix buffer account")]
+    InvalidBuffer,
+    #[msg("anyix buffer authority mismatch")]
+    BufferAuthorityMismatch,
+    #[msg("write exceeds anyix buffer payload")]
+    BufferOverflow,
+    #[msg("anyix bundle expired")]
+    BundleExpired,
+    #[msg("anyix bundle already complete")]
+    BundleComplete,
+    #[msg("invalid anyix config account")]
+    InvalidConfig,
+    #[msg("anyix authority did not sign")]
+    Unauthorized,
+    #[msg("anyix nonce mismatch")]
+    NonceMismatch,
+    #[msg("invalid anyix nonce account")]
+    InvalidNonceAccount,
+    #[msg("anyix bundle is missing a nonce")]
+    MissingNonce,
+    #[msg("anyix bundle signature not found")]
+    SignatureNotFound,
+    #[msg("anyix accounts do not match commitment")]
+    AccountCommitmentMismatch,
+    #[msg("too many accounts for anyix bundle")]
+    TrailingAccounts,
+    #[msg("anyix bundle is missing an account commitment")]
+    MissingAccountCommitment,
+}
+
+impl From<AnyIxError> for AnyIxErrorCode {
+    fn from(err: AnyIxError) -> Self {
+        match err {
+            AnyIxError::TruncatedHeader => AnyIxErrorCode::TruncatedHeader,
+            AnyIxError::DataUnderflow => AnyIxErrorCode::DataUnderflow,
+            AnyIxError::TrailingBytes => AnyIxErrorCode::TrailingBytes,
+            AnyIxError::AccountUnderflow => AnyIxErrorCode::AccountUnderflow,
+            AnyIxError::SelfInvocation => AnyIxErrorCode::SelfInvocation,
+            AnyIxError::UnsupportedVersion => AnyIxErrorCode::UnsupportedVersion,
+            AnyIxError::LengthMismatch => AnyIxErrorCode::LengthMismatch,
+            AnyIxError::LengthOverflow => AnyIxErrorCode::LengthOverflow,
+            AnyIxError::InvalidExtension => AnyIxErrorCode::InvalidExtension,
+            AnyIxError::SignerNotAllowed => AnyIxErrorCode::SignerNotAllowed,
+            AnyIxError::InvalidSignerSeeds => AnyIxErrorCode::InvalidSignerSeeds,
+            AnyIxError::SignerNamespace => AnyIxErrorCode::SignerNamespace,
+            AnyIxError::ProgramNotAllowed => AnyIxErrorCode::ProgramNotAllowed,
+            AnyIxError::InstructionNotAllowed => AnyIxErrorCode::InstructionNotAllowed,
+            AnyIxError::SignerForwardingNotAllowed => AnyIxErrorCode::SignerForwardingNotAllowed,
+            AnyIxError::ReturnDataMismatch => AnyIxErrorCode::ReturnDataMismatch,
+            AnyIxError::PatchOutOfBounds => AnyIxErrorCode::PatchOutOfBounds,
+            AnyIxError::AssertionFailed => AnyIxErrorCode::AssertionFailed,
+            AnyIxError::InvalidTokenAccount => AnyIxErrorCode::InvalidTokenAccount,
+            AnyIxError::PreconditionFailed => AnyIxErrorCode::PreconditionFailed,
+            AnyIxError::InvalidBuffer => AnyIxErrorCode::InvalidBuffer,
+            AnyIxError::BufferAuthorityMismatch => AnyIxErrorCode::BufferAuthorityMismatch,
+            AnyIxError::BufferOverflow => AnyIxErrorCode::BufferOverflow,
+            AnyIxError::BundleExpired => AnyIxErrorCode::BundleExpired,
+            AnyIxError::BundleComplete => AnyIxErrorCode::BundleComplete,
+            AnyIxError::InvalidConfig => AnyIxErrorCode::InvalidConfig,
+            AnyIxError::Unauthorized => AnyIxErrorCode::Unauthorized,
+            AnyIxError::NonceMismatch => AnyIxErrorCode::NonceMismatch,
+            AnyIxError::InvalidNonceAccount => AnyIxErrorCode::InvalidNonceAccount,
+            AnyIxError::MissingNonce => AnyIxErrorCode::MissingNonce,
+            AnyIxError::SignatureNotFound => AnyIxErrorCode::SignatureNotFound,
+            AnyIxError::AccountCommitmentMismatch => AnyIxErrorCode::AccountCommitmentMismatch,
+            AnyIxError::TrailingAccounts => AnyIxErrorCode::TrailingAccounts,
+            AnyIxError::MissingAccountCommitment => AnyIxErrorCode::MissingAccountCommitment,
+        }
+    }
+}
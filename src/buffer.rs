@@ -0,0 +1,345 @@
+//! buffer accounts allow executing bundles which are too large to fit within the
+//! data of a single instruction. a buffer is created by the client from a fresh
+//! keypair, with enough space for the payload, and assigned to the executing
+//! program, which then initializes it with `handle_init_buffer` in the same
+//! transaction, signed by the buffer so that no existing account of the program
+//! can be claimed as a buffer. the payload is written in chunks with
+//! `handle_write_buffer`, and finally executed with `handle_execute_buffer`, which
+//! closes the buffer and refunds its rent.
+//!
+//! a bundle which needs more compute than a single transaction allows can instead
+//! be stored in the buffer and driven across several transactions with
+//! `handle_execute_next`, which advances a cursor over the instructions.
+//!
+//! the payload is executed in place rather than copied to the heap, so the
+//! buffer is borrowed while the instructions are invoked, and passing it to one
+//! of them fails. the data of each instruction is still copied before it is
+//! invoked, and as the heap of a program is 32 KiB and never freed, the combined
+//! size of the instructions executed by a single call is limited accordingly.
+//!
+//! a buffer is laid out as
+//! `[initialized u8][authority 32][payload len u32][cursor u16][expiry slot u64][payload]`,
+//! where an expiry slot of 0 means the buffer never expires
+
+use std::cell::Ref;
+
+use solana_program::account_info::AccountInfo;
+use solana_program::clock::Clock;
+use solana_program::entrypoint::ProgramResult;
+use solana_program::msg;
+use solana_program::program_error::ProgramError;
+use solana_program::pubkey::Pubkey;
+use solana_program::rent::Rent;
+use solana_program::sysvar::Sysvar;
+
+use crate::{AnyIxError, AnyIxPolicy, AnyIxRef, Executor};
+
+/// the first byte of an initialized buffer
+pub const BUFFER_INITIALIZED: u8 = 1;
+
+/// the number of bytes preceding the payload of a buffer
+pub const BUFFER_HEADER_LEN: usize = 1 + 32 + 4 + 2 + 8;
+
+/// returns the account size required for a buffer holding a payload of `payload_len` bytes
+pub fn buffer_size(payload_len: usize) -> usize {
+    BUFFER_HEADER_LEN + payload_len
+}
+
+/// splits a packed payload into chunks of at most `chunk_len` bytes, along with
+/// the offset of each chunk, to be written with `handle_write_buffer`
+pub fn buffer_chunks(payload: &[u8], chunk_len: usize) -> impl Iterator<Item = (u32, &[u8])> {
+    payload
+        .chunks(chunk_len)
+        .enumerate()
+        .map(move |(idx, chunk)| ((idx * chunk_len) as u32, chunk))
+}
+
+/// the header of an initialized buffer account
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BufferHeader {
+    /// the account which may write to and execute the buffer
+    pub authority: Pubkey,
+    pub payload_len: u32,
+    /// the index of the next instruction executed by `handle_execute_next`
+    pub cursor: u16,
+    /// the last slot at which the buffer may be executed, if any
+    pub expiry_slot: Option<u64>,
+}
+
+impl BufferHeader {
+    /// decodes the header of an initialized buffer from the data of the account
+    pub fn unpack(data: &[u8]) -> Result<Self, AnyIxError> {
+        if data.len() < BUFFER_HEADER_LEN || data[0] != BUFFER_INITIALIZED {
+            return Err(AnyIxError::InvalidBuffer);
+        }
+        let mut authority = [0u8; 32];
+        authority.copy_from_slice(&data[1..33]);
+        let mut expiry_slot = [0u8; 8];
+        expiry_slot.copy_from_slice(&data[39..47]);
+        let header = BufferHeader {
+            authority: Pubkey::new_from_array(authority),
+            payload_len: u32::from_le_bytes([data[33], data[34], data[35], data[36]]),
+            cursor: u16::from_le_bytes([data[37], data[38]]),
+            expiry_slot: Some(u64::from_le_bytes(expiry_slot)).filter(|slot| *slot != 0),
+        };
+        if data.len() < buffer_size(header.payload_len as usize) {
+            return Err(AnyIxError::InvalidBuffer);
+        }
+        Ok(header)
+    }
+    /// encodes the header into the start of the data of the account
+    pub fn pack_into(&self, data: &mut [u8]) {
+        data[0] = BUFFER_INITIALIZED;
+        data[1..33].copy_from_slice(self.authority.as_ref());
+        data[33..37].copy_from_slice(&self.payload_len.to_le_bytes());
+        data[37..39].copy_from_slice(&self.cursor.to_le_bytes());
+        data[39..47].copy_from_slice(&self.expiry_slot.unwrap_or_default().to_le_bytes());
+    }
+}
+
+/// returns true once `handle_execute_next` has executed every instruction of the
+/// bundle held by a buffer, given the data of the buffer account
+pub fn is_complete(data: &[u8]) -> Result<bool, AnyIxError> {
+    let header = BufferHeader::unpack(data)?;
+    let payload = &data[BUFFER_HEADER_LEN..buffer_size(header.payload_len as usize)];
+    Ok(header.cursor >= AnyIxRef::unpack(payload)?.num_instructions())
+}
+
+/// initializes `buffer`, which must sign, be owned by `program_id`, rent exempt and
+/// large enough to hold `payload_len` bytes, allowing `authority` to write to and execute it
+pub fn handle_init_buffer(
+    program_id: Pubkey,
+    buffer: &AccountInfo<'_>,
+    authority: &AccountInfo<'_>,
+    payload_len: u32,
+) -> ProgramResult {
+    handle_init_buffer_with_expiry(program_id, buffer, authority, payload_len, None)
+}
+
+/// same as `handle_init_buffer`, but rejects executing the buffer after `expiry_slot`
+pub fn handle_init_buffer_with_expiry(
+    program_id: Pubkey,
+    buffer: &AccountInfo<'_>,
+    authority: &AccountInfo<'_>,
+    payload_len: u32,
+    expiry_slot: Option<u64>,
+) -> ProgramResult {
+    if !authority.is_signer || !buffer.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    if !buffer.owner.eq(&program_id) {
+        return Err(AnyIxError::InvalidBuffer.into());
+    }
+    let mut data = buffer.try_borrow_mut_data()?;
+    if data.len() < buffer_size(payload_len as usize) || data[0] != 0 {
+        return Err(AnyIxError::InvalidBuffer.into());
+    }
+    if !Rent::get()?.is_exempt(buffer.lamports(), data.len()) {
+        return Err(ProgramError::AccountNotRentExempt);
+    }
+    BufferHeader {
+        authority: *authority.key,
+        payload_len,
+        cursor: 0,
+        expiry_slot,
+    }
+    .pack_into(&mut data);
+    Ok(())
+}
+
+/// writes `bytes` into the payload of `buffer`, starting at `offset`. writes are
+/// rejected once `handle_execute_next` has started executing the buffer
+pub fn handle_write_buffer(
+    program_id: Pubkey,
+    buffer: &AccountInfo<'_>,
+    authority: &AccountInfo<'_>,
+    offset: u32,
+    bytes: &[u8],
+) -> ProgramResult {
+    let mut data = buffer.try_borrow_mut_data()?;
+    let header = check_buffer(&program_id, buffer, &data, authority)?;
+    if header.cursor != 0 {
+        return Err(AnyIxError::InvalidBuffer.into());
+    }
+    let offset = offset as usize;
+    if offset + bytes.len() > header.payload_len as usize {
+        return Err(AnyIxError::BufferOverflow.into());
+    }
+    let start = BUFFER_HEADER_LEN + offset;
+    data[start..start + bytes.len()].copy_from_slice(bytes);
+    Ok(())
+}
+
+/// executes the payload of `buffer` as `handle_anyix` would, then closes the
+/// buffer, transferring its lamports to `destination`
+pub fn handle_execute_buffer<'info>(
+    program_id: Pubkey,
+    buffer: &AccountInfo<'info>,
+    authority: &AccountInfo<'info>,
+    destination: &AccountInfo<'info>,
+    accounts: &[AccountInfo<'info>],
+    policy: &dyn AnyIxPolicy,
+) -> ProgramResult {
+    if buffer.key.eq(destination.key) {
+        return Err(AnyIxError::InvalidBuffer.into());
+    }
+    let (header, payload) = load_payload(&program_id, buffer, authority)?;
+    // a partially executed bundle must be finished with `handle_execute_next`
+    if header.cursor != 0 {
+        return Err(AnyIxError::InvalidBuffer.into());
+    }
+    Executor::new(&program_id, policy).execute(accounts, &AnyIxRef::unpack(&payload)?)?;
+    drop(payload);
+    close_buffer(buffer, destination)
+}
+
+/// executes the next `count` instructions of the bundle stored in `buffer`,
+/// advancing its cursor, where `accounts` holds the accounts of the whole bundle,
+/// as passed to `handle_execute_buffer`. once every instruction has been executed
+/// the buffer can be closed with `handle_close_buffer`.
+///
+/// the valid until slot and account commitment are evaluated by every call, while
+/// preconditions are only checked and a nonce only consumed by the first call.
+/// return data can not be patched into an instruction executed by a different
+/// call, and bundles with balance assertions can not be executed incrementally,
+/// as a balance can not be compared across calls
+pub fn handle_execute_next<'info>(
+    program_id: Pubkey,
+    buffer: &AccountInfo<'info>,
+    authority: &AccountInfo<'info>,
+    accounts: &[AccountInfo<'info>],
+    policy: &dyn AnyIxPolicy,
+    count: u16,
+) -> ProgramResult {
+    let (mut header, payload) = load_payload(&program_id, buffer, authority)?;
+    let arb_ix = AnyIxRef::unpack(&payload)?;
+    let num_instructions = arb_ix.num_instructions();
+    if header.cursor >= num_instructions {
+        return Err(AnyIxError::BundleComplete.into());
+    }
+    let count = count.min(num_instructions - header.cursor);
+    Executor::new(&program_id, policy)
+        .with_window(header.cursor, count)
+        .execute(accounts, &arb_ix)?;
+    drop(payload);
+
+    header.cursor += count;
+    header.pack_into(&mut buffer.try_borrow_mut_data()?);
+    msg!("anyix cursor {} of {}", header.cursor, num_instructions);
+    Ok(())
+}
+
+/// closes `buffer` without executing it, transferring its lamports to `destination`
+pub fn handle_close_buffer<'info>(
+    program_id: Pubkey,
+    buffer: &AccountInfo<'info>,
+    authority: &AccountInfo<'info>,
+    destination: &AccountInfo<'info>,
+) -> ProgramResult {
+    if buffer.key.eq(destination.key) {
+        return Err(AnyIxError::InvalidBuffer.into());
+    }
+    check_buffer(&program_id, buffer, &buffer.try_borrow_data()?, authority)?;
+    close_buffer(buffer, destination)
+}
+
+// checks the buffer is initialized and owned by the program, and that
+// `authority` is its authority and has signed, returning the header
+fn check_buffer(
+    program_id: &Pubkey,
+    buffer: &AccountInfo<'_>,
+    data: &[u8],
+    authority: &AccountInfo<'_>,
+) -> Result<BufferHeader, ProgramError> {
+    if !authority.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    if !buffer.owner.eq(program_id) {
+        return Err(AnyIxError::InvalidBuffer.into());
+    }
+    let header = BufferHeader::unpack(data)?;
+    if !authority.key.eq(&header.authority) {
+        return Err(AnyIxError::BufferAuthorityMismatch.into());
+    }
+    Ok(header)
+}
+
+// checks the buffer may be executed, returning its header and a borrow of its
+// payload, which must be dropped before the buffer is written to
+fn load_payload<'a>(
+    program_id: &Pubkey,
+    buffer: &'a AccountInfo<'_>,
+    authority: &AccountInfo<'_>,
+) -> Result<(BufferHeader, Ref<'a, [u8]>), ProgramError> {
+    let data = buffer.try_borrow_data()?;
+    let header = check_buffer(program_id, buffer, &data, authority)?;
+    if let Some(expiry_slot) = header.expiry_slot {
+        if Clock::get()?.slot > expiry_slot {
+            return Err(AnyIxError::BundleExpired.into());
+        }
+    }
+    let end = buffer_size(header.payload_len as usize);
+    let payload = Ref::map(data, |data| &data[BUFFER_HEADER_LEN..end]);
+    Ok((header, payload))
+}
+
+// transfers the lamports of the buffer to `destination` and clears its data
+fn close_buffer(buffer: &AccountInfo<'_>, destination: &AccountInfo<'_>) -> ProgramResult {
+    let lamports = buffer.lamports();
+    **destination.try_borrow_mut_lamports()? = destination
+        .lamports()
+        .checked_add(lamports)
+        .ok_or(ProgramError::ArithmeticOverflow)?;
+    **buffer.try_borrow_mut_lamports()? = 0;
+    buffer.try_borrow_mut_data()?.fill(0);
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_buffer_header() {
+        let header = BufferHeader {
+            authority: Pubkey::new_unique(),
+            payload_len: 3,
+            cursor: 1,
+            expiry_slot: Some(10),
+        };
+        let mut data = vec![0; buffer_size(3)];
+        header.pack_into(&mut data);
+        assert_eq!(data[0], BUFFER_INITIALIZED);
+        assert_eq!(BufferHeader::unpack(&data), Ok(header));
+        // an expiry slot of 0 means the buffer never expires
+        let header = BufferHeader {
+            expiry_slot: None,
+            ..header
+        };
+        header.pack_into(&mut data);
+        assert_eq!(data[39..47], [0; 8]);
+        assert_eq!(BufferHeader::unpack(&data), Ok(header));
+
+        // the account must hold the whole payload
+        assert_eq!(
+            BufferHeader::unpack(&data[..buffer_size(2)]),
+            Err(AnyIxError::InvalidBuffer)
+        );
+        data[0] = crate::config::CONFIG_INITIALIZED;
+        assert_eq!(BufferHeader::unpack(&data), Err(AnyIxError::InvalidBuffer));
+    }
+
+    #[test]
+    fn test_buffer_chunks() {
+        let payload = (0..10u8).collect::<Vec<_>>();
+        let chunks = buffer_chunks(&payload, 4).collect::<Vec<_>>();
+        assert_eq!(
+            chunks,
+            vec![
+                (0, &payload[0..4]),
+                (4, &payload[4..8]),
+                (8, &payload[8..10])
+            ]
+        );
+    }
+}
@@ -0,0 +1,204 @@
+use std::collections::{HashMap, HashSet};
+
+use solana_program::instruction::{AccountMeta, Instruction};
+use solana_program::pubkey::Pubkey;
+
+use crate::hash::hash_account_metas;
+use crate::{AnyIx, AnyIxError, Extension, SignerSeeds};
+
+/// client side helper which converts a set of instructions into an AnyIx payload
+/// along with the accounts that must be passed to `handle_anyix`.
+///
+/// every instruction is given the account layout `handle_anyix` expects, namely the
+/// program account followed by the instruction's own accounts
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct AnyIxBuilder {
+    instructions: Vec<Instruction>,
+    extensions: Vec<Extension>,
+    /// PDAs the executing program signs for, which can not sign the outer instruction
+    signer_keys: Vec<Pubkey>,
+    /// accounts passed after those of the instructions, such as a nonce account
+    accounts: Vec<AccountMeta>,
+    /// whether to commit to the accounts returned by `account_metas`
+    commit_accounts: bool,
+    /// whether accounts are listed once and referenced by index
+    account_table: bool,
+}
+
+impl AnyIxBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// appends an instruction to the bundle
+    pub fn add_instruction(&mut self, ix: Instruction) -> &mut Self {
+        self.instructions.push(ix);
+        self
+    }
+    /// appends an instruction which the executing program signs for as the PDA
+    /// `signer_key`, derived from `seeds` and `bump`. the first seed must be the
+    /// namespace the program passes to `handle_anyix_signed_with_config`.
+    ///
+    /// `signer_key` is marked as a signer of the inner instruction only, as it
+    /// can not sign the outer instruction
+    pub fn add_signed_instruction(
+        &mut self,
+        ix: Instruction,
+        signer_key: Pubkey,
+        seeds: Vec<Vec<u8>>,
+        bump: u8,
+    ) -> &mut Self {
+        self.extensions.push(Extension::Signer(SignerSeeds {
+            instruction: self.instructions.len() as u16,
+            seeds,
+            bump,
+        }));
+        self.signer_keys.push(signer_key);
+        self.add_instruction(ix)
+    }
+    /// appends an extension to the bundle, such as a `ReturnDataPatch`. extensions
+    /// reference instructions by their index in the order they were added
+    pub fn add_extension(&mut self, extension: Extension) -> &mut Self {
+        self.extensions.push(extension);
+        self
+    }
+    /// appends an account after the accounts of every instruction, so that it can be
+    /// referenced by extensions such as a nonce extension. its index is the number of
+    /// accounts returned by `account_metas` before it was added
+    pub fn add_account(&mut self, account: AccountMeta) -> &mut Self {
+        self.accounts.push(account);
+        self
+    }
+    /// encodes the bundle with an account table, where `account_metas` lists every
+    /// pubkey once and each instruction references its accounts by their u8 index
+    /// into the table, as transaction messages do. bundles sharing accounts such as
+    /// a token program or owner between instructions are considerably smaller
+    pub fn use_account_table(&mut self) -> &mut Self {
+        self.account_table = true;
+        self
+    }
+    /// adds an account commitment extension to the bundle, computed over the
+    /// accounts returned by `account_metas` when the bundle is built. the
+    /// commitment only prevents whoever submits the transaction from substituting
+    /// accounts if the bundle itself is authenticated, such as a bundle signed for
+    /// `handle_anyix_ed25519`, which requires one. otherwise the submitter can
+    /// simply remove the extension.
+    ///
+    /// the executor compares the commitment against the privileges the runtime
+    /// grants each account, so the transaction must not add to the flags returned
+    /// by `account_metas`, for example by using one of the accounts as the fee payer
+    pub fn commit_accounts(&mut self) -> &mut Self {
+        self.commit_accounts = true;
+        self
+    }
+    /// appends multiple instructions to the bundle
+    pub fn add_instructions(&mut self, ixs: impl IntoIterator<Item = Instruction>) -> &mut Self {
+        self.instructions.extend(ixs);
+        self
+    }
+    /// returns the instructions which have been added to the bundle
+    pub fn instructions(&self) -> &[Instruction] {
+        &self.instructions
+    }
+    /// returns the AnyIx for the bundle, with each account count including
+    /// the program account
+    pub fn anyix(&self) -> Result<AnyIx, AnyIxError> {
+        let to_u16 = |value: usize| u16::try_from(value).map_err(|_| AnyIxError::LengthOverflow);
+        let mut instruction_data_sizes = Vec::with_capacity(self.instructions.len());
+        let mut instruction_account_counts = Vec::with_capacity(self.instructions.len());
+        for ix in self.instructions.iter() {
+            instruction_data_sizes.push(to_u16(ix.data.len())?);
+            instruction_account_counts.push(to_u16(ix.accounts.len() + 1)?);
+        }
+        let mut extensions = self.extensions.clone();
+        if self.account_table {
+            extensions.push(Extension::AccountIndices(self.account_indices()?));
+        }
+        if self.commit_accounts {
+            extensions.push(Extension::AccountCommitment(hash_account_metas(
+                &self.account_metas(),
+            )));
+        }
+        Ok(AnyIx {
+            num_instructions: to_u16(self.instructions.len())?,
+            instruction_data_sizes,
+            instruction_account_counts,
+            instruction_datas: self.instructions.iter().map(|ix| ix.data.clone()).collect(),
+            extensions,
+        })
+    }
+    // returns the index of every account of every instruction into the account table
+    fn account_indices(&self) -> Result<Vec<u8>, AnyIxError> {
+        let table = self
+            .account_metas()
+            .into_iter()
+            .enumerate()
+            .map(|(idx, meta)| {
+                Ok((
+                    meta.pubkey,
+                    u8::try_from(idx).map_err(|_| AnyIxError::LengthOverflow)?,
+                ))
+            })
+            .collect::<Result<HashMap<_, _>, AnyIxError>>()?;
+        Ok(self
+            .instructions
+            .iter()
+            .flat_map(|ix| {
+                std::iter::once(&ix.program_id).chain(ix.accounts.iter().map(|meta| &meta.pubkey))
+            })
+            .map(|pubkey| table[pubkey])
+            .collect())
+    }
+    /// returns the flattened list of accounts to pass to `handle_anyix`, where each
+    /// instruction's accounts are preceded by its program account, followed by the
+    /// accounts added with `add_account`.
+    ///
+    /// a pubkey used by multiple instructions is listed once per use, or only once
+    /// when using an account table, with every occurrence carrying the union of the
+    /// signer and writable flags, matching the privileges the runtime grants to the
+    /// account for the whole transaction. PDAs signed for by the executing program
+    /// are never marked as signers
+    pub fn account_metas(&self) -> Vec<AccountMeta> {
+        let metas = self
+            .instructions
+            .iter()
+            .flat_map(|ix| {
+                std::iter::once(AccountMeta::new_readonly(ix.program_id, false))
+                    .chain(ix.accounts.iter().cloned())
+            })
+            .chain(self.accounts.iter().cloned())
+            .collect::<Vec<_>>();
+        let mut flags: HashMap<Pubkey, (bool, bool)> = HashMap::with_capacity(metas.len());
+        for meta in metas.iter() {
+            let (is_signer, is_writable) = flags.entry(meta.pubkey).or_default();
+            *is_signer |= meta.is_signer;
+            *is_writable |= meta.is_writable;
+        }
+        let mut listed = HashSet::with_capacity(flags.len());
+        metas
+            .into_iter()
+            .filter(|meta| !self.account_table || listed.insert(meta.pubkey))
+            .map(|meta| {
+                let (is_signer, is_writable) = flags[&meta.pubkey];
+                AccountMeta {
+                    pubkey: meta.pubkey,
+                    is_signer: is_signer && !self.signer_keys.contains(&meta.pubkey),
+                    is_writable,
+                }
+            })
+            .collect()
+    }
+    /// returns the packed AnyIx payload, and the accounts to pass to `handle_anyix`
+    pub fn build(&self) -> Result<(Vec<u8>, Vec<AccountMeta>), AnyIxError> {
+        Ok((self.anyix()?.pack()?, self.account_metas()))
+    }
+    /// wraps the bundle into a single instruction targeting `program_id`, which is
+    /// expected to pass its instruction data and accounts to `handle_anyix`
+    pub fn instruction(&self, program_id: Pubkey) -> Result<Instruction, AnyIxError> {
+        let (data, accounts) = self.build()?;
+        Ok(Instruction {
+            program_id,
+            accounts,
+            data,
+        })
+    }
+}
@@ -0,0 +1,300 @@
+//! config accounts restrict who may execute bundles through a program, by
+//! holding a list of authorities of which at least one must sign. a program has
+//! a single config, the PDA at `config_address`, which is created and initialized
+//! by `handle_init_config`. as whoever initializes the config chooses its
+//! authorities, the program passes the key allowed to initialize it, which it
+//! hard codes, so that the config can not be claimed by someone else.
+//!
+//! a config is laid out as `[initialized u8][num authorities u8][authorities 32 * n]`
+
+use solana_program::account_info::AccountInfo;
+use solana_program::entrypoint::ProgramResult;
+use solana_program::program::{invoke, invoke_signed};
+use solana_program::program_error::ProgramError;
+use solana_program::pubkey::Pubkey;
+use solana_program::rent::Rent;
+use solana_program::system_instruction;
+use solana_program::sysvar::Sysvar;
+
+use crate::{handle_anyix_signed, AnyIxError, AnyIxPolicy, AnyIxRef, Executor};
+
+/// the first byte of an initialized config
+pub const CONFIG_INITIALIZED: u8 = 2;
+
+/// the seed of the config account of a program
+pub const CONFIG_SEED: &[u8] = b"anyix-config";
+
+/// returns the address of the config account of `program_id`, along with its bump
+pub fn config_address(program_id: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[CONFIG_SEED], program_id)
+}
+
+/// the number of bytes preceding the authorities of a config
+pub const CONFIG_HEADER_LEN: usize = 2;
+
+/// the authorities permitted to execute bundles through a program
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct AnyIxConfig {
+    pub authorities: Vec<Pubkey>,
+}
+
+impl AnyIxConfig {
+    /// returns the account size required for a config holding `num_authorities` authorities
+    pub fn size(num_authorities: usize) -> usize {
+        CONFIG_HEADER_LEN + num_authorities * 32
+    }
+    /// decodes an initialized config from the data of the account
+    pub fn unpack(data: &[u8]) -> Result<Self, AnyIxError> {
+        if data.len() < CONFIG_HEADER_LEN || data[0] != CONFIG_INITIALIZED {
+            return Err(AnyIxError::InvalidConfig);
+        }
+        let num_authorities = data[1] as usize;
+        let authorities = data
+            .get(CONFIG_HEADER_LEN..Self::size(num_authorities))
+            .ok_or(AnyIxError::InvalidConfig)?
+            .chunks_exact(32)
+            .map(|key| Pubkey::try_from(key).unwrap())
+            .collect();
+        Ok(AnyIxConfig { authorities })
+    }
+    /// encodes the config into the data of the account, which must be large enough
+    /// to hold every authority
+    pub fn pack_into(&self, data: &mut [u8]) -> Result<(), AnyIxError> {
+        let num_authorities =
+            u8::try_from(self.authorities.len()).map_err(|_| AnyIxError::LengthOverflow)?;
+        if self.authorities.is_empty() || data.len() < Self::size(self.authorities.len()) {
+            return Err(AnyIxError::InvalidConfig);
+        }
+        data[0] = CONFIG_INITIALIZED;
+        data[1] = num_authorities;
+        for (authority, key) in self
+            .authorities
+            .iter()
+            .zip(data[CONFIG_HEADER_LEN..].chunks_exact_mut(32))
+        {
+            key.copy_from_slice(authority.as_ref());
+        }
+        Ok(())
+    }
+    /// returns true if one of the authorities is a signer of `accounts`
+    pub fn is_authorized(&self, accounts: &[AccountInfo<'_>]) -> bool {
+        accounts
+            .iter()
+            .any(|account| account.is_signer && self.authorities.contains(account.key))
+    }
+}
+
+/// same as `handle_anyix`, but rejects the bundle unless one of the authorities
+/// held by `config` is a signer of `accounts`
+pub fn handle_anyix_with_config<'info>(
+    program_id: Pubkey,
+    config: &AccountInfo<'info>,
+    accounts: &[AccountInfo<'info>],
+    data: &[u8],
+    policy: &dyn AnyIxPolicy,
+) -> ProgramResult {
+    if !load_config(&program_id, config)?.is_authorized(accounts) {
+        return Err(AnyIxError::Unauthorized.into());
+    }
+    Executor::new(&program_id, policy).execute(accounts, &AnyIxRef::unpack(data)?)
+}
+
+/// same as `handle_anyix_with_config`, but additionally allows the bundle to sign
+/// for PDAs of `program_id` using the seeds carried in its signer extensions.
+///
+/// the first seed of every signer must equal `signer_namespace`, which must not be
+/// empty, so that an authority can only make the program sign for PDAs the program
+/// has reserved for anyix. as seeds are concatenated when deriving a PDA, the
+/// namespace only restricts a byte prefix: seeds `["vault", "-a"]` derive the same
+/// PDA as `["vault-a"]`, so no other PDA of the program may have seeds starting
+/// with the bytes of the namespace
+pub fn handle_anyix_signed_with_config<'info>(
+    program_id: Pubkey,
+    config: &AccountInfo<'info>,
+    accounts: &[AccountInfo<'info>],
+    data: &[u8],
+    signer_namespace: &[u8],
+    policy: &dyn AnyIxPolicy,
+) -> ProgramResult {
+    if !load_config(&program_id, config)?.is_authorized(accounts) {
+        return Err(AnyIxError::Unauthorized.into());
+    }
+    handle_anyix_signed(program_id, accounts, data, signer_namespace, policy)
+}
+
+/// creates the config of `program_id` at `config_address`, with room for
+/// `max_authorities` authorities, and initializes it with `authorities`.
+/// `authority` must be `expected_authority`, one of the authorities, must sign,
+/// and pays for the account
+pub fn handle_init_config<'info>(
+    program_id: Pubkey,
+    config: &AccountInfo<'info>,
+    authority: &AccountInfo<'info>,
+    system_program: &AccountInfo<'info>,
+    expected_authority: &Pubkey,
+    authorities: &[Pubkey],
+    max_authorities: u8,
+) -> ProgramResult {
+    if !authority.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    if !authority.key.eq(expected_authority) || !authorities.contains(authority.key) {
+        return Err(AnyIxError::Unauthorized.into());
+    }
+    let (address, bump) = config_address(&program_id);
+    if !config.key.eq(&address) || authorities.len() > max_authorities as usize {
+        return Err(AnyIxError::InvalidConfig.into());
+    }
+    if config.owner.eq(&solana_program::system_program::id()) {
+        create_config(
+            &program_id,
+            config,
+            authority,
+            system_program,
+            bump,
+            AnyIxConfig::size(max_authorities as usize),
+        )?;
+    }
+    if !config.owner.eq(&program_id) {
+        return Err(AnyIxError::InvalidConfig.into());
+    }
+    let mut data = config.try_borrow_mut_data()?;
+    if data.first() != Some(&0) {
+        return Err(AnyIxError::InvalidConfig.into());
+    }
+    AnyIxConfig {
+        authorities: authorities.to_vec(),
+    }
+    .pack_into(&mut data)?;
+    Ok(())
+}
+
+/// replaces the authorities held by `config`, signed by one of the current authorities
+pub fn handle_set_authorities(
+    program_id: Pubkey,
+    config: &AccountInfo<'_>,
+    authority: &AccountInfo<'_>,
+    authorities: &[Pubkey],
+) -> ProgramResult {
+    check_authority(&load_config(&program_id, config)?, authority)?;
+    AnyIxConfig {
+        authorities: authorities.to_vec(),
+    }
+    .pack_into(&mut config.try_borrow_mut_data()?)?;
+    Ok(())
+}
+
+/// replaces `authority`, one of the authorities held by `config`, with `new_authority`
+pub fn handle_rotate_authority(
+    program_id: Pubkey,
+    config: &AccountInfo<'_>,
+    authority: &AccountInfo<'_>,
+    new_authority: Pubkey,
+) -> ProgramResult {
+    let mut anyix_config = load_config(&program_id, config)?;
+    check_authority(&anyix_config, authority)?;
+    for key in anyix_config.authorities.iter_mut() {
+        if *key == *authority.key {
+            *key = new_authority;
+        }
+    }
+    anyix_config.pack_into(&mut config.try_borrow_mut_data()?)?;
+    Ok(())
+}
+
+// decodes the config, checking it is the config of the program
+pub(crate) fn load_config(
+    program_id: &Pubkey,
+    config: &AccountInfo<'_>,
+) -> Result<AnyIxConfig, ProgramError> {
+    if !config.owner.eq(program_id) || !config.key.eq(&config_address(program_id).0) {
+        return Err(AnyIxError::InvalidConfig.into());
+    }
+    Ok(AnyIxConfig::unpack(&config.try_borrow_data()?)?)
+}
+
+// checks `authority` is one of the authorities of the config, and has signed
+fn check_authority(config: &AnyIxConfig, authority: &AccountInfo<'_>) -> ProgramResult {
+    if !authority.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    if !config.authorities.contains(authority.key) {
+        return Err(AnyIxError::Unauthorized.into());
+    }
+    Ok(())
+}
+
+// creates the config account, rent exempt and owned by the program, paid for by
+// `payer`. lamports sent to the address beforehand can not block its creation
+fn create_config<'info>(
+    program_id: &Pubkey,
+    config: &AccountInfo<'info>,
+    payer: &AccountInfo<'info>,
+    system_program: &AccountInfo<'info>,
+    bump: u8,
+    space: usize,
+) -> ProgramResult {
+    let lamports = Rent::get()?.minimum_balance(space);
+    let accounts = [payer.clone(), config.clone(), system_program.clone()];
+    let seeds: &[&[u8]] = &[CONFIG_SEED, &[bump]];
+    if config.lamports() == 0 {
+        return invoke_signed(
+            &system_instruction::create_account(
+                payer.key,
+                config.key,
+                lamports,
+                space as u64,
+                program_id,
+            ),
+            &accounts,
+            &[seeds],
+        );
+    }
+    let top_up = lamports.saturating_sub(config.lamports());
+    if top_up > 0 {
+        invoke(
+            &system_instruction::transfer(payer.key, config.key, top_up),
+            &accounts,
+        )?;
+    }
+    invoke_signed(
+        &system_instruction::allocate(config.key, space as u64),
+        &accounts,
+        &[seeds],
+    )?;
+    invoke_signed(
+        &system_instruction::assign(config.key, program_id),
+        &accounts,
+        &[seeds],
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_config_layout() {
+        let config = AnyIxConfig {
+            authorities: vec![Pubkey::new_unique(), Pubkey::new_unique()],
+        };
+        let mut data = vec![0; AnyIxConfig::size(2)];
+        assert_eq!(
+            config.pack_into(&mut data[..AnyIxConfig::size(1)]),
+            Err(AnyIxError::InvalidConfig)
+        );
+        config.pack_into(&mut data).unwrap();
+        assert_eq!(data[..CONFIG_HEADER_LEN], [CONFIG_INITIALIZED, 2]);
+        assert_eq!(AnyIxConfig::unpack(&data), Ok(config));
+        assert_eq!(
+            AnyIxConfig::unpack(&data[..AnyIxConfig::size(1)]),
+            Err(AnyIxError::InvalidConfig)
+        );
+        assert_eq!(
+            AnyIxConfig::default().pack_into(&mut data),
+            Err(AnyIxError::InvalidConfig)
+        );
+        data[0] = crate::nonce::NONCE_INITIALIZED;
+        assert_eq!(AnyIxConfig::unpack(&data), Err(AnyIxError::InvalidConfig));
+    }
+}
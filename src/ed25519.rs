@@ -0,0 +1,140 @@
+//! bundles signed off-chain by an authority, and submitted on their behalf by a
+//! relayer. the transaction carries an Ed25519 program instruction verifying the
+//! authority's signature over `bundle_message`, which `handle_anyix_ed25519` finds
+//! by introspecting the instructions sysvar.
+//!
+//! signed bundles must carry a nonce extension, so that a relayer can not
+//! submit the same bundle twice, and an account commitment, so that a relayer
+//! can not execute the bundle with accounts other than those the authority
+//! signed for. as the nonce extension holds the address of its nonce account,
+//! the signed bundle pins both the nonce account and every other account
+
+use solana_program::account_info::AccountInfo;
+use solana_program::ed25519_program;
+use solana_program::entrypoint::ProgramResult;
+use solana_program::hash::hashv;
+use solana_program::instruction::Instruction;
+use solana_program::pubkey::Pubkey;
+use solana_program::sysvar::instructions::{
+    load_current_index_checked, load_instruction_at_checked,
+};
+
+use crate::config::load_config;
+use crate::{AnyIxError, AnyIxPolicy, AnyIxRef, Executor, Extension};
+
+/// the offset of the signature offsets within Ed25519 program instruction data,
+/// following the signature count and a padding byte
+const SIGNATURE_OFFSETS_START: usize = 2;
+
+/// the size of the offsets of each signature within Ed25519 program instruction data
+const SIGNATURE_OFFSETS_LEN: usize = 14;
+
+/// returns the message an authority signs to authorize a bundle, the sha256 hash
+/// of the packed bundle, its nonce in little endian, and the program executing it
+pub fn bundle_message(packed: &[u8], nonce: u64, program_id: &Pubkey) -> [u8; 32] {
+    hashv(&[packed, &nonce.to_le_bytes(), program_id.as_ref()]).to_bytes()
+}
+
+/// returns an Ed25519 program instruction verifying `signature`, made by `pubkey`
+/// over `message`, which must precede the instruction calling `handle_anyix_ed25519`
+pub fn ed25519_instruction(pubkey: &Pubkey, signature: &[u8; 64], message: &[u8]) -> Instruction {
+    let public_key_offset = SIGNATURE_OFFSETS_START + SIGNATURE_OFFSETS_LEN;
+    let signature_offset = public_key_offset + 32;
+    let message_data_offset = signature_offset + 64;
+    let mut data = vec![1, 0];
+    for value in [
+        signature_offset as u16,
+        u16::MAX,
+        public_key_offset as u16,
+        u16::MAX,
+        message_data_offset as u16,
+        message.len() as u16,
+        u16::MAX,
+    ] {
+        data.extend_from_slice(&value.to_le_bytes());
+    }
+    data.extend_from_slice(pubkey.as_ref());
+    data.extend_from_slice(signature);
+    data.extend_from_slice(message);
+    Instruction {
+        program_id: ed25519_program::id(),
+        accounts: vec![],
+        data,
+    }
+}
+
+/// same as `handle_anyix`, but rejects the bundle unless an earlier instruction of
+/// the transaction verifies a signature over `bundle_message` made by one of the
+/// authorities held by `config`. the bundle must carry a nonce extension, whose
+/// nonce account is incremented by the execution, and an account commitment
+pub fn handle_anyix_ed25519<'info>(
+    program_id: Pubkey,
+    config: &AccountInfo<'info>,
+    instructions_sysvar: &AccountInfo<'info>,
+    accounts: &[AccountInfo<'info>],
+    data: &[u8],
+    policy: &dyn AnyIxPolicy,
+) -> ProgramResult {
+    let arb_ix = AnyIxRef::unpack(data)?;
+    let nonce = arb_ix
+        .extensions()
+        .find_map(|extension| match extension {
+            Ok(Extension::Nonce(nonce)) => Some(nonce.nonce),
+            _ => None,
+        })
+        .ok_or(AnyIxError::MissingNonce)?;
+    if !arb_ix
+        .extensions()
+        .any(|extension| matches!(extension, Ok(Extension::AccountCommitment(_))))
+    {
+        return Err(AnyIxError::MissingAccountCommitment.into());
+    }
+    let message = bundle_message(data, nonce, &program_id);
+    let authorities = load_config(&program_id, config)?.authorities;
+
+    let current_index = load_current_index_checked(instructions_sysvar)?;
+    let mut authorized = false;
+    for idx in 0..current_index {
+        let ix = load_instruction_at_checked(idx as usize, instructions_sysvar)?;
+        if !ix.program_id.eq(&ed25519_program::id()) {
+            continue;
+        }
+        if verified_signers(&ix.data, &message).any(|signer| authorities.contains(&signer)) {
+            authorized = true;
+            break;
+        }
+    }
+    if !authorized {
+        return Err(AnyIxError::SignatureNotFound.into());
+    }
+    Executor::new(&program_id, policy).execute(accounts, &arb_ix)
+}
+
+// returns the keys which signed `message`, according to the data of an Ed25519
+// program instruction. only signatures whose key, signature and message are all
+// held by the instruction itself are considered
+fn verified_signers<'a>(data: &'a [u8], message: &'a [u8]) -> impl Iterator<Item = Pubkey> + 'a {
+    let num_signatures = data.first().copied().unwrap_or_default() as usize;
+    (0..num_signatures).filter_map(move |idx| {
+        let start = SIGNATURE_OFFSETS_START + idx * SIGNATURE_OFFSETS_LEN;
+        let offsets = data.get(start..start + SIGNATURE_OFFSETS_LEN)?;
+        let offsets = offsets
+            .chunks_exact(2)
+            .map(|value| u16::from_le_bytes([value[0], value[1]]) as usize)
+            .collect::<Vec<_>>();
+        let (public_key_offset, message_data_offset, message_data_size) =
+            (offsets[2], offsets[4], offsets[5]);
+        if [offsets[1], offsets[3], offsets[6]]
+            .iter()
+            .any(|instruction_index| *instruction_index != u16::MAX as usize)
+        {
+            return None;
+        }
+        let signed = data.get(message_data_offset..message_data_offset + message_data_size)?;
+        if signed != message {
+            return None;
+        }
+        let public_key = data.get(public_key_offset..public_key_offset + 32)?;
+        Pubkey::try_from(public_key).ok()
+    })
+}
@@ -0,0 +1,184 @@
+use std::fmt::Display;
+
+use solana_program::program_error::ProgramError;
+
+/// offset applied to every AnyIxError when converted into `ProgramError::Custom`,
+/// chosen so that the codes do not collide with those of the host program.
+/// the codes are stable, new variants are only ever appended
+pub const ERROR_CODE_OFFSET: u32 = 0x414e_0000;
+
+/// errors returned while decoding, encoding or executing an AnyIx bundle
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[repr(u32)]
+pub enum AnyIxError {
+    /// the input ended before the instruction count, data sizes or account counts
+    TruncatedHeader = 0,
+    /// the input ended before all instruction data could be read
+    DataUnderflow = 1,
+    /// the input contained bytes after the last instruction data
+    TrailingBytes = 2,
+    /// fewer accounts were provided than the bundle requires
+    AccountUnderflow = 3,
+    /// an inner instruction attempted to invoke the executing program
+    SelfInvocation = 4,
+    /// the payload used a version tag which is not understood
+    UnsupportedVersion = 5,
+    /// the number of data sizes, account counts or datas does not match the instruction count
+    LengthMismatch = 6,
+    /// a count or size does not fit within the encoding being used
+    LengthOverflow = 7,
+    /// an extension is of an unknown kind, malformed, or references a missing instruction
+    InvalidExtension = 8,
+    /// the bundle requested signed invocation from an executor that does not permit it
+    SignerNotAllowed = 9,
+    /// signer seeds do not derive to an account of the instruction being signed for
+    InvalidSignerSeeds = 10,
+    /// signer seeds are outside of the namespace permitted by the executor
+    SignerNamespace = 11,
+    /// the policy rejected invoking the program of an inner instruction
+    ProgramNotAllowed = 12,
+    /// the policy rejected an inner instruction
+    InstructionNotAllowed = 13,
+    /// the policy rejected forwarding a transaction signer into an inner instruction
+    SignerForwardingNotAllowed = 14,
+    /// no return data was set by the program of the instruction being patched from
+    ReturnDataMismatch = 15,
+    /// a patch reads or writes outside of the source or target bytes
+    PatchOutOfBounds = 16,
+    /// a balance assertion was violated once every instruction had been invoked
+    AssertionFailed = 17,
+    /// a token balance assertion references an account which is not a token account
+    InvalidTokenAccount = 18,
+    /// the state of an account did not satisfy a precondition of the bundle
+    PreconditionFailed = 19,
+    /// a buffer account is not owned by the program, is too small, or is not in the expected state
+    InvalidBuffer = 20,
+    /// the authority does not match the authority of the buffer account
+    BufferAuthorityMismatch = 21,
+    /// a write extends past the end of the payload of a buffer account
+    BufferOverflow = 22,
+    /// a buffer account or bundle was executed after its expiry slot
+    BundleExpired = 23,
+    /// every instruction of a stored bundle has already been executed
+    BundleComplete = 24,
+    /// a config account is not owned by the program, is too small, or is not in the expected state
+    InvalidConfig = 25,
+    /// none of the authorities of a config account signed
+    Unauthorized = 26,
+    /// the nonce of a bundle does not match the nonce held by its nonce account
+    NonceMismatch = 27,
+    /// a nonce account is not owned by the program, is not writable, or is not initialized
+    InvalidNonceAccount = 28,
+    /// a bundle which must carry a nonce extension does not
+    MissingNonce = 29,
+    /// no earlier instruction verifies an authority's signature over the bundle
+    SignatureNotFound = 30,
+    /// the accounts passed to the executor do not match the account commitment of the bundle
+    AccountCommitmentMismatch = 31,
+    /// more accounts were provided than the bundle uses
+    TrailingAccounts = 32,
+    /// a bundle which must carry an account commitment does not
+    MissingAccountCommitment = 33,
+}
+
+impl AnyIxError {
+    /// every error variant, in code order
+    pub const ALL: [AnyIxError; 34] = [
+        AnyIxError::TruncatedHeader,
+        AnyIxError::DataUnderflow,
+        AnyIxError::TrailingBytes,
+        AnyIxError::AccountUnderflow,
+        AnyIxError::SelfInvocation,
+        AnyIxError::UnsupportedVersion,
+        AnyIxError::LengthMismatch,
+        AnyIxError::LengthOverflow,
+        AnyIxError::InvalidExtension,
+        AnyIxError::SignerNotAllowed,
+        AnyIxError::InvalidSignerSeeds,
+        AnyIxError::SignerNamespace,
+        AnyIxError::ProgramNotAllowed,
+        AnyIxError::InstructionNotAllowed,
+        AnyIxError::SignerForwardingNotAllowed,
+        AnyIxError::ReturnDataMismatch,
+        AnyIxError::PatchOutOfBounds,
+        AnyIxError::AssertionFailed,
+        AnyIxError::InvalidTokenAccount,
+        AnyIxError::PreconditionFailed,
+        AnyIxError::InvalidBuffer,
+        AnyIxError::BufferAuthorityMismatch,
+        AnyIxError::BufferOverflow,
+        AnyIxError::BundleExpired,
+        AnyIxError::BundleComplete,
+        AnyIxError::InvalidConfig,
+        AnyIxError::Unauthorized,
+        AnyIxError::NonceMismatch,
+        AnyIxError::InvalidNonceAccount,
+        AnyIxError::MissingNonce,
+        AnyIxError::SignatureNotFound,
+        AnyIxError::AccountCommitmentMismatch,
+        AnyIxError::TrailingAccounts,
+        AnyIxError::MissingAccountCommitment,
+    ];
+    /// returns the code used for `ProgramError::Custom`
+    pub fn code(self) -> u32 {
+        ERROR_CODE_OFFSET + self as u32
+    }
+    /// maps a `ProgramError::Custom` code back into an AnyIxError, returning None
+    /// if the code was not produced by this crate
+    pub fn from_code(code: u32) -> Option<Self> {
+        let idx = code.checked_sub(ERROR_CODE_OFFSET)?;
+        AnyIxError::ALL.get(idx as usize).copied()
+    }
+}
+
+impl Display for AnyIxError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let msg = match self {
+            AnyIxError::TruncatedHeader => "anyix header is truncated",
+            AnyIxError::DataUnderflow => "anyix instruction data is truncated",
+            AnyIxError::TrailingBytes => "anyix payload has trailing bytes",
+            AnyIxError::AccountUnderflow => "not enough accounts for anyix bundle",
+            AnyIxError::SelfInvocation => "self invocation not allowed",
+            AnyIxError::UnsupportedVersion => "unsupported anyix version",
+            AnyIxError::LengthMismatch => "anyix lengths do not match instruction count",
+            AnyIxError::LengthOverflow => "anyix length overflows encoding",
+            AnyIxError::InvalidExtension => "invalid anyix extension",
+            AnyIxError::SignerNotAllowed => "signed invocation not allowed",
+            AnyIxError::InvalidSignerSeeds => "signer seeds do not match instruction accounts",
+            AnyIxError::SignerNamespace => "signer seeds outside of permitted namespace",
+            AnyIxError::ProgramNotAllowed => "program not allowed by anyix policy",
+            AnyIxError::InstructionNotAllowed => "instruction not allowed by anyix policy",
+            AnyIxError::SignerForwardingNotAllowed => {
+                "signer forwarding not allowed by anyix policy"
+            }
+            AnyIxError::ReturnDataMismatch => "return data not set by the expected program",
+            AnyIxError::PatchOutOfBounds => "anyix patch out of bounds",
+            AnyIxError::AssertionFailed => "anyix balance assertion failed",
+            AnyIxError::InvalidTokenAccount => "anyix assertion account is not a token account",
+            AnyIxError::PreconditionFailed => "anyix precondition failed",
+            AnyIxError::InvalidBuffer => "invalid anyix buffer account",
+            AnyIxError::BufferAuthorityMismatch => "anyix buffer authority mismatch",
+            AnyIxError::BufferOverflow => "write exceeds anyix buffer payload",
+            AnyIxError::BundleExpired => "anyix bundle expired",
+            AnyIxError::BundleComplete => "anyix bundle already complete",
+            AnyIxError::InvalidConfig => "invalid anyix config account",
+            AnyIxError::Unauthorized => "anyix authority did not sign",
+            AnyIxError::NonceMismatch => "anyix nonce mismatch",
+            AnyIxError::InvalidNonceAccount => "invalid anyix nonce account",
+            AnyIxError::MissingNonce => "anyix bundle is missing a nonce",
+            AnyIxError::SignatureNotFound => "anyix bundle signature not found",
+            AnyIxError::AccountCommitmentMismatch => "anyix accounts do not match commitment",
+            AnyIxError::TrailingAccounts => "too many accounts for anyix bundle",
+            AnyIxError::MissingAccountCommitment => "anyix bundle is missing an account commitment",
+        };
+        f.write_str(msg)
+    }
+}
+
+impl std::error::Error for AnyIxError {}
+
+impl From<AnyIxError> for ProgramError {
+    fn from(err: AnyIxError) -> Self {
+        ProgramError::Custom(err.code())
+    }
+}
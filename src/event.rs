@@ -0,0 +1,208 @@
+//! machine readable execution events, emitted with `sol_log_data` when the
+//! `events` feature is enabled. every invoked instruction is followed by an
+//! instruction event, and a successful execution ends with a summary event.
+//!
+//! each event is logged as a single base64 encoded `Program data:` record,
+//! starting with an anchor style event discriminator, followed by
+//!
+//! * instruction: `[index u16][program id 32][account count u16][data hash 32][compute units u64]`
+//! * summary: `[invoked u16][skipped u16][compute units u64]`
+//!
+//! where every integer is little endian, and compute units are those consumed
+//! according to `sol_remaining_compute_units`. use `decode_events`, enabled by the
+//! `client` feature, to recover the events from the log messages of a transaction
+
+#[cfg(feature = "client")]
+use base64::{engine::general_purpose::STANDARD, Engine};
+use solana_program::pubkey::Pubkey;
+
+/// `sighash("event", "AnyIxInstructionEvent")`
+pub const INSTRUCTION_EVENT_DISCRIMINATOR: [u8; 8] = [232, 100, 152, 28, 39, 103, 29, 250];
+
+/// `sighash("event", "AnyIxSummaryEvent")`
+pub const SUMMARY_EVENT_DISCRIMINATOR: [u8; 8] = [176, 62, 84, 98, 246, 179, 252, 24];
+
+/// an event emitted while executing a bundle
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum AnyIxEvent {
+    Instruction(InstructionEvent),
+    Summary(SummaryEvent),
+}
+
+/// emitted after an inner instruction has been invoked
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct InstructionEvent {
+    /// the index of the instruction within the bundle
+    pub index: u16,
+    pub program_id: Pubkey,
+    /// the number of accounts passed to the instruction, excluding the program
+    pub account_count: u16,
+    /// the sha256 hash of the instruction data, after any patches were applied
+    pub data_hash: [u8; 32],
+    pub compute_units: u64,
+}
+
+/// emitted once every instruction of the bundle, or of the window being
+/// executed, has been invoked or skipped
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SummaryEvent {
+    pub invoked: u16,
+    /// the number of instructions skipped because a guard did not hold
+    pub skipped: u16,
+    /// the compute units consumed by the whole execution, including the checks
+    /// made by the executor
+    pub compute_units: u64,
+}
+
+impl AnyIxEvent {
+    /// encodes the event into the bytes of a `sol_log_data` record
+    pub fn pack(&self) -> Vec<u8> {
+        let mut data = Vec::new();
+        match self {
+            AnyIxEvent::Instruction(event) => {
+                data.extend_from_slice(&INSTRUCTION_EVENT_DISCRIMINATOR);
+                data.extend_from_slice(&event.index.to_le_bytes());
+                data.extend_from_slice(event.program_id.as_ref());
+                data.extend_from_slice(&event.account_count.to_le_bytes());
+                data.extend_from_slice(&event.data_hash);
+                data.extend_from_slice(&event.compute_units.to_le_bytes());
+            }
+            AnyIxEvent::Summary(event) => {
+                data.extend_from_slice(&SUMMARY_EVENT_DISCRIMINATOR);
+                data.extend_from_slice(&event.invoked.to_le_bytes());
+                data.extend_from_slice(&event.skipped.to_le_bytes());
+                data.extend_from_slice(&event.compute_units.to_le_bytes());
+            }
+        }
+        data
+    }
+    /// decodes a `sol_log_data` record, returning None if it is not an anyix event
+    pub fn unpack(data: &[u8]) -> Option<Self> {
+        if data.len() < 8 {
+            return None;
+        }
+        let (discriminator, body) = data.split_at(8);
+        if discriminator == INSTRUCTION_EVENT_DISCRIMINATOR {
+            let body: &[u8; 76] = body.try_into().ok()?;
+            return Some(AnyIxEvent::Instruction(InstructionEvent {
+                index: u16::from_le_bytes([body[0], body[1]]),
+                program_id: Pubkey::try_from(&body[2..34]).ok()?,
+                account_count: u16::from_le_bytes([body[34], body[35]]),
+                data_hash: body[36..68].try_into().ok()?,
+                compute_units: u64::from_le_bytes(body[68..76].try_into().ok()?),
+            }));
+        }
+        if discriminator == SUMMARY_EVENT_DISCRIMINATOR {
+            let body: &[u8; 12] = body.try_into().ok()?;
+            return Some(AnyIxEvent::Summary(SummaryEvent {
+                invoked: u16::from_le_bytes([body[0], body[1]]),
+                skipped: u16::from_le_bytes([body[2], body[3]]),
+                compute_units: u64::from_le_bytes(body[4..12].try_into().ok()?),
+            }));
+        }
+        None
+    }
+}
+
+/// returns the events emitted by `program_id`, given the log messages of a
+/// transaction. records logged by the programs it invokes are ignored, so an
+/// inner instruction can not forge the events of the executing program
+#[cfg(feature = "client")]
+pub fn decode_events(program_id: &Pubkey, logs: &[String]) -> Vec<AnyIxEvent> {
+    let program_id = program_id.to_string();
+    // the programs currently executing, innermost last
+    let mut invoked: Vec<&str> = Vec::new();
+    let mut events = Vec::new();
+    for log in logs {
+        let Some(message) = log.strip_prefix("Program ") else {
+            continue;
+        };
+        if let Some(fields) = message.strip_prefix("data: ") {
+            if invoked.last() != Some(&program_id.as_str()) {
+                continue;
+            }
+            events.extend(
+                fields
+                    .split(' ')
+                    .filter_map(|field| STANDARD.decode(field).ok())
+                    .filter_map(|data| AnyIxEvent::unpack(&data)),
+            );
+            continue;
+        }
+        let mut words = message.split(' ');
+        match (words.next(), words.next()) {
+            (Some(program), Some("invoke")) => invoked.push(program),
+            (Some(_), Some("success" | "failed:")) => {
+                invoked.pop();
+            }
+            _ => {}
+        }
+    }
+    events
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_pack_unpack() {
+        let events = [
+            AnyIxEvent::Instruction(InstructionEvent {
+                index: 1,
+                program_id: Pubkey::new_unique(),
+                account_count: 2,
+                data_hash: solana_program::hash::hash(&[1, 2]).to_bytes(),
+                compute_units: 1000,
+            }),
+            AnyIxEvent::Summary(SummaryEvent {
+                invoked: 2,
+                skipped: 1,
+                compute_units: 3000,
+            }),
+        ];
+        for event in &events {
+            let packed = event.pack();
+            assert_eq!(AnyIxEvent::unpack(&packed).as_ref(), Some(event));
+            assert_eq!(AnyIxEvent::unpack(&packed[..packed.len() - 1]), None);
+        }
+        assert_eq!(AnyIxEvent::unpack(&[0; 20]), None);
+    }
+
+    #[cfg(feature = "client")]
+    #[test]
+    fn test_decode_events() {
+        let (program_id, inner_program) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let instruction_event = AnyIxEvent::Instruction(InstructionEvent {
+            index: 0,
+            program_id: inner_program,
+            account_count: 1,
+            data_hash: [0; 32],
+            compute_units: 1000,
+        });
+        let summary_event = AnyIxEvent::Summary(SummaryEvent {
+            invoked: 1,
+            skipped: 0,
+            compute_units: 1500,
+        });
+        let record =
+            |event: &AnyIxEvent| format!("Program data: {}", STANDARD.encode(event.pack()));
+        let logs = vec![
+            format!("Program {program_id} invoke [1]"),
+            "Program log: total accounts 3".to_string(),
+            format!("Program {inner_program} invoke [2]"),
+            // records of invoked programs are not attributed to the executing program
+            record(&summary_event),
+            format!("Program {inner_program} success"),
+            record(&instruction_event),
+            "Program data: AAAA".to_string(),
+            record(&summary_event),
+            format!("Program {program_id} success"),
+            record(&instruction_event),
+        ];
+        assert_eq!(
+            decode_events(&program_id, &logs),
+            vec![instruction_event, summary_event]
+        );
+    }
+}
@@ -0,0 +1,578 @@
+use std::borrow::Cow;
+
+use solana_program::account_info::AccountInfo;
+use solana_program::clock::Clock;
+use solana_program::entrypoint::ProgramResult;
+use solana_program::hash::hash;
+use solana_program::instruction::{AccountMeta, Instruction};
+use solana_program::log::sol_log_data;
+use solana_program::msg;
+use solana_program::program::get_return_data;
+use solana_program::program_error::ProgramError;
+use solana_program::pubkey::Pubkey;
+use solana_program::sysvar::Sysvar;
+
+use crate::event::{AnyIxEvent, InstructionEvent, SummaryEvent};
+use crate::extension::{
+    SPL_TOKEN_2022_ACCOUNT_TYPE, SPL_TOKEN_2022_PROGRAM_ID, SPL_TOKEN_ACCOUNT_LEN,
+    SPL_TOKEN_AMOUNT_OFFSET, SPL_TOKEN_PROGRAM_ID,
+};
+use crate::hash::hash_account_infos;
+use crate::nonce::advance_nonce;
+use crate::{
+    AccountCheck, AnyIxError, AnyIxPolicy, AnyIxRef, Assertion, Balance, Extension, Precondition,
+};
+
+/// executes the instructions of a bundle on behalf of `program_id`, with the
+/// protections configured by the public `handle_anyix` entrypoints
+pub(crate) struct Executor<'a> {
+    program_id: &'a Pubkey,
+    policy: &'a dyn AnyIxPolicy,
+    /// the first seed required of every signer, signing is rejected when None
+    signer_namespace: Option<&'a [u8]>,
+    /// whether inner instructions may invoke `program_id`
+    allow_self_invocation: bool,
+    /// the range of instructions to invoke, all of them when None
+    window: Option<(usize, usize)>,
+    /// whether to reject accounts which are not used by the bundle
+    exact_accounts: bool,
+}
+
+impl<'a> Executor<'a> {
+    pub(crate) fn new(program_id: &'a Pubkey, policy: &'a dyn AnyIxPolicy) -> Self {
+        Executor {
+            program_id,
+            policy,
+            signer_namespace: None,
+            allow_self_invocation: false,
+            window: None,
+            exact_accounts: false,
+        }
+    }
+    pub(crate) fn with_exact_accounts(mut self) -> Self {
+        self.exact_accounts = true;
+        self
+    }
+    /// restricts execution to `count` instructions starting at `start`. `accounts`
+    /// still holds the accounts of the whole bundle, so that extensions index them
+    /// as they would when executing every instruction
+    pub(crate) fn with_window(mut self, start: u16, count: u16) -> Self {
+        self.window = Some((start as usize, start as usize + count as usize));
+        self
+    }
+    fn in_window(&self, idx: usize) -> bool {
+        self.window
+            .map_or(true, |(start, end)| idx >= start && idx < end)
+    }
+    pub(crate) fn with_signer_namespace(mut self, signer_namespace: &'a [u8]) -> Self {
+        self.signer_namespace = Some(signer_namespace);
+        self
+    }
+    #[cfg(feature = "unsafe-exec")]
+    pub(crate) fn with_self_invocation(mut self) -> Self {
+        self.allow_self_invocation = true;
+        self
+    }
+    pub(crate) fn execute(
+        &self,
+        accounts: &[AccountInfo<'_>],
+        arb_ix: &AnyIxRef<'_>,
+    ) -> ProgramResult {
+        msg!("total accounts {}", accounts.len());
+        let start_units = remaining_compute_units();
+        let extensions = arb_ix.extensions().collect::<Result<Vec<_>, _>>()?;
+        self.validate_extensions(arb_ix, &extensions)?;
+        // checks made before any instruction is invoked. the nonce of a stored
+        // bundle is only consumed, and its preconditions only checked, by the call
+        // executing its first instruction
+        for extension in extensions.iter() {
+            match extension {
+                Extension::AccountCommitment(commitment)
+                    if hash_account_infos(accounts).ne(commitment) =>
+                {
+                    return Err(AnyIxError::AccountCommitmentMismatch.into());
+                }
+                Extension::ValidUntil(slot) if Clock::get()?.slot > *slot => {
+                    return Err(AnyIxError::BundleExpired.into());
+                }
+                Extension::Precondition(precondition) if self.in_window(0) => {
+                    check_precondition(accounts, precondition)?
+                }
+                _ => {}
+            }
+        }
+        // the nonce is only consumed once every other check has passed
+        for extension in extensions.iter() {
+            let Extension::Nonce(nonce) = extension else {
+                continue;
+            };
+            if !self.in_window(0) {
+                continue;
+            }
+            let nonce_account = accounts
+                .get(nonce.account as usize)
+                .ok_or(AnyIxError::AccountUnderflow)?;
+            if !nonce_account.key.eq(&nonce.nonce_account) {
+                return Err(AnyIxError::InvalidNonceAccount.into());
+            }
+            advance_nonce(self.program_id, nonce_account, nonce.nonce)?;
+        }
+        // balances are snapshotted before the first instruction is invoked
+        let assertions = extensions
+            .iter()
+            .filter_map(|extension| match extension {
+                Extension::Assertion(assertion) => Some(assertion),
+                _ => None,
+            })
+            .map(|assertion| Ok((assertion, balance_of(accounts, assertion)?)))
+            .collect::<Result<Vec<_>, ProgramError>>()?;
+        // bytes to write into the data of a later instruction, as (target, offset, bytes)
+        let mut pending_patches: Vec<(u16, u16, Vec<u8>)> = Vec::new();
+        let indices = extensions.iter().find_map(|extension| match extension {
+            Extension::AccountIndices(indices) => Some(&indices[..]),
+            _ => None,
+        });
+        if self.exact_accounts {
+            self.check_accounts_consumed(accounts, arb_ix, &extensions, indices)?;
+        }
+        let mut offset = 0;
+        let (mut invoked, mut skipped) = (0u16, 0u16);
+        for (idx, (account_count, ix_data)) in arb_ix.iter().enumerate() {
+            // the accounts of instructions outside the window are skipped over
+            let ix_accounts = offset..offset + account_count as usize;
+            offset = ix_accounts.end;
+            if !self.in_window(idx) {
+                continue;
+            }
+            // accounts are either resolved through the account table, or sliced
+            let cpi_accounts: Cow<[AccountInfo<'_>]> = match indices {
+                Some(indices) => Cow::Owned(
+                    indices[ix_accounts]
+                        .iter()
+                        .map(|account_idx| accounts.get(*account_idx as usize).cloned())
+                        .collect::<Option<Vec<_>>>()
+                        .ok_or(AnyIxError::AccountUnderflow)?,
+                ),
+                None => Cow::Borrowed(
+                    accounts
+                        .get(ix_accounts)
+                        .ok_or(AnyIxError::AccountUnderflow)?,
+                ),
+            };
+            let program_account = cpi_accounts.first().ok_or(AnyIxError::AccountUnderflow)?;
+
+            if !guards_hold(idx, accounts, &extensions)? {
+                msg!("skipping anyix(idx={})", idx);
+                // a later instruction can not be patched with return data that was never set
+                if extensions.iter().any(|extension| {
+                    matches!(extension, Extension::ReturnDataPatch(patch) if patch.source as usize == idx)
+                }) {
+                    return Err(AnyIxError::ReturnDataMismatch.into());
+                }
+                pending_patches.retain(|(target, _, _)| *target as usize != idx);
+                skipped += 1;
+                continue;
+            }
+            let mut ix_data = ix_data.to_vec();
+            pending_patches.retain(|(target, target_offset, bytes)| {
+                if *target as usize != idx {
+                    return true;
+                }
+                let target_offset = *target_offset as usize;
+                ix_data[target_offset..target_offset + bytes.len()].copy_from_slice(bytes);
+                false
+            });
+            apply_account_patches(idx, accounts, &extensions, &mut ix_data)?;
+            // the policy sees the data exactly as it will be invoked
+            self.check_instruction(idx, program_account, &cpi_accounts[1..], &ix_data)?;
+            let (signer_keys, signer_seeds) = self.signers(idx, &cpi_accounts[1..], &extensions)?;
+
+            msg!(
+                "processing anyix(idx={}, num_accounts={}, offset={})",
+                idx,
+                cpi_accounts.len(),
+                offset
+            );
+            let ix = Instruction {
+                program_id: *program_account.key,
+                accounts: cpi_accounts[1..]
+                    .iter()
+                    .map(|account| {
+                        let is_signer = account.is_signer || signer_keys.contains(account.key);
+                        if account.is_writable {
+                            AccountMeta::new(*account.key, is_signer)
+                        } else {
+                            AccountMeta::new_readonly(*account.key, is_signer)
+                        }
+                    })
+                    .collect(),
+                data: ix_data,
+            };
+            let ix_units = remaining_compute_units();
+            solana_program::program::invoke_signed(
+                &ix,
+                &cpi_accounts[1..],
+                &signer_seeds
+                    .iter()
+                    .map(|seeds| &seeds[..])
+                    .collect::<Vec<_>>(),
+            )?;
+            invoked += 1;
+            if cfg!(feature = "events") {
+                let compute_units = ix_units.saturating_sub(remaining_compute_units());
+                log_event(&AnyIxEvent::Instruction(InstructionEvent {
+                    index: idx as u16,
+                    program_id: ix.program_id,
+                    account_count: ix.accounts.len() as u16,
+                    data_hash: hash(&ix.data).to_bytes(),
+                    compute_units,
+                }));
+            }
+
+            for extension in extensions.iter() {
+                let Extension::ReturnDataPatch(patch) = extension else {
+                    continue;
+                };
+                if patch.source as usize != idx {
+                    continue;
+                }
+                let (return_program, return_data) = get_return_data()
+                    .filter(|(return_program, _)| return_program.eq(program_account.key))
+                    .ok_or(AnyIxError::ReturnDataMismatch)?;
+                let source_offset = patch.source_offset as usize;
+                let bytes = return_data
+                    .get(source_offset..source_offset + patch.len as usize)
+                    .ok_or(AnyIxError::PatchOutOfBounds)?;
+                msg!(
+                    "patching anyix(idx={}) with return data of {}",
+                    patch.target,
+                    return_program
+                );
+                pending_patches.push((patch.target, patch.target_offset, bytes.to_vec()));
+            }
+        }
+        for (assertion, before) in assertions {
+            let after = balance_of(accounts, assertion)?;
+            if !assertion.check.check(before, after) {
+                msg!(
+                    "anyix assertion failed(account={}, before={}, after={})",
+                    assertion.account,
+                    before,
+                    after
+                );
+                return Err(AnyIxError::AssertionFailed.into());
+            }
+        }
+        if cfg!(feature = "events") {
+            log_event(&AnyIxEvent::Summary(SummaryEvent {
+                invoked,
+                skipped,
+                compute_units: start_units.saturating_sub(remaining_compute_units()),
+            }));
+        }
+        Ok(())
+    }
+    // checks every extension is permitted and references valid instructions,
+    // before any instruction is invoked
+    fn validate_extensions(
+        &self,
+        arb_ix: &AnyIxRef<'_>,
+        extensions: &[Extension],
+    ) -> Result<(), AnyIxError> {
+        let target_fits = |target: u16, target_offset: u16, len: u16| {
+            target_offset as usize + len as usize <= arb_ix.data_size(target as usize) as usize
+        };
+        let mut num_account_indices = 0;
+        for extension in extensions.iter() {
+            if extension
+                .instruction()
+                .is_some_and(|idx| idx >= arb_ix.num_instructions())
+            {
+                return Err(AnyIxError::InvalidExtension);
+            }
+            match extension {
+                Extension::Signer(signer) => {
+                    let signer_namespace =
+                        self.signer_namespace.ok_or(AnyIxError::SignerNotAllowed)?;
+                    if signer.seeds.first().map(|seed| &seed[..]) != Some(signer_namespace) {
+                        return Err(AnyIxError::SignerNamespace);
+                    }
+                }
+                Extension::ReturnDataPatch(patch) => {
+                    if patch.source >= patch.target {
+                        return Err(AnyIxError::InvalidExtension);
+                    }
+                    // return data does not outlive the window it was set in
+                    if self.in_window(patch.source as usize)
+                        != self.in_window(patch.target as usize)
+                    {
+                        return Err(AnyIxError::ReturnDataMismatch);
+                    }
+                    if !target_fits(patch.target, patch.target_offset, patch.len) {
+                        return Err(AnyIxError::PatchOutOfBounds);
+                    }
+                }
+                Extension::AccountDataPatch(patch) => {
+                    if !target_fits(patch.target, patch.target_offset, patch.len) {
+                        return Err(AnyIxError::PatchOutOfBounds);
+                    }
+                }
+                // a balance can not be snapshotted in one window and checked in another
+                Extension::Assertion(_) if self.window.is_some() => {
+                    return Err(AnyIxError::InvalidExtension);
+                }
+                Extension::AccountIndices(indices) => {
+                    let num_accounts: usize = arb_ix
+                        .iter()
+                        .map(|(account_count, _)| account_count as usize)
+                        .sum();
+                    if indices.len() != num_accounts || num_account_indices > 0 {
+                        return Err(AnyIxError::InvalidExtension);
+                    }
+                    num_account_indices += 1;
+                }
+                Extension::Assertion(_)
+                | Extension::Precondition(_)
+                | Extension::Guard(_)
+                | Extension::Nonce(_)
+                | Extension::ValidUntil(_)
+                | Extension::AccountCommitment(_) => {}
+            }
+        }
+        Ok(())
+    }
+    // checks the bundle uses exactly the accounts provided, either through its
+    // instructions or through the accounts its extensions reference
+    fn check_accounts_consumed(
+        &self,
+        accounts: &[AccountInfo<'_>],
+        arb_ix: &AnyIxRef<'_>,
+        extensions: &[Extension],
+        indices: Option<&[u8]>,
+    ) -> Result<(), AnyIxError> {
+        let instruction_accounts = match indices {
+            Some(indices) => indices
+                .iter()
+                .map(|account_idx| *account_idx as usize + 1)
+                .max()
+                .unwrap_or_default(),
+            None => arb_ix
+                .iter()
+                .enumerate()
+                .filter(|(idx, _)| self.in_window(*idx))
+                .map(|(_, (account_count, _))| account_count as usize)
+                .sum(),
+        };
+        let extension_accounts = extensions
+            .iter()
+            .filter_map(|extension| extension.account())
+            .map(|account_idx| account_idx as usize + 1)
+            .max()
+            .unwrap_or_default();
+        let consumed = instruction_accounts.max(extension_accounts);
+        if accounts.len() < consumed {
+            return Err(AnyIxError::AccountUnderflow);
+        }
+        if accounts.len() > consumed {
+            return Err(AnyIxError::TrailingAccounts);
+        }
+        Ok(())
+    }
+    // applies the self invocation check and the policy to an instruction
+    fn check_instruction(
+        &self,
+        idx: usize,
+        program_account: &AccountInfo<'_>,
+        ix_accounts: &[AccountInfo<'_>],
+        ix_data: &[u8],
+    ) -> Result<(), AnyIxError> {
+        if !self.allow_self_invocation && self.program_id.eq(program_account.key) {
+            return Err(AnyIxError::SelfInvocation);
+        }
+        if !self.policy.allow_program(program_account.key) {
+            return Err(AnyIxError::ProgramNotAllowed);
+        }
+        if !self
+            .policy
+            .allow_instruction(idx, program_account, ix_accounts, ix_data)
+        {
+            return Err(AnyIxError::InstructionNotAllowed);
+        }
+        if ix_accounts
+            .iter()
+            .any(|account| account.is_signer && !self.policy.allow_signer_forwarding(account))
+        {
+            return Err(AnyIxError::SignerForwardingNotAllowed);
+        }
+        Ok(())
+    }
+    // returns the PDAs signed for while invoking the instruction at `idx`, along
+    // with their seeds, verifying each PDA is one of the instruction's accounts
+    #[allow(clippy::type_complexity)]
+    fn signers<'e>(
+        &self,
+        idx: usize,
+        ix_accounts: &[AccountInfo<'_>],
+        extensions: &'e [Extension],
+    ) -> Result<(Vec<Pubkey>, Vec<Vec<&'e [u8]>>), AnyIxError> {
+        let mut signer_keys = Vec::new();
+        let mut signer_seeds = Vec::new();
+        let signers = extensions.iter().filter_map(|extension| match extension {
+            Extension::Signer(signer) if signer.instruction as usize == idx => Some(signer),
+            _ => None,
+        });
+        for signer in signers {
+            let seeds = signer
+                .seeds
+                .iter()
+                .map(|seed| &seed[..])
+                .chain(std::iter::once(std::slice::from_ref(&signer.bump)))
+                .collect::<Vec<_>>();
+            let signer_key = Pubkey::create_program_address(&seeds, self.program_id)
+                .map_err(|_| AnyIxError::InvalidSignerSeeds)?;
+            if !ix_accounts.iter().any(|account| signer_key.eq(account.key)) {
+                return Err(AnyIxError::InvalidSignerSeeds);
+            }
+            signer_keys.push(signer_key);
+            signer_seeds.push(seeds);
+        }
+        Ok((signer_keys, signer_seeds))
+    }
+}
+
+// copies the current data of referenced accounts into the data of the
+// instruction at `idx`, immediately before it is invoked
+fn apply_account_patches(
+    idx: usize,
+    accounts: &[AccountInfo<'_>],
+    extensions: &[Extension],
+    ix_data: &mut [u8],
+) -> ProgramResult {
+    for extension in extensions.iter() {
+        let Extension::AccountDataPatch(patch) = extension else {
+            continue;
+        };
+        if patch.target as usize != idx {
+            continue;
+        }
+        let account = accounts
+            .get(patch.account as usize)
+            .ok_or(AnyIxError::AccountUnderflow)?;
+        let account_data = account.try_borrow_data()?;
+        let source_offset = patch.source_offset as usize;
+        let bytes = account_data
+            .get(source_offset..source_offset + patch.len as usize)
+            .ok_or(AnyIxError::PatchOutOfBounds)?;
+        let target_offset = patch.target_offset as usize;
+        ix_data[target_offset..target_offset + bytes.len()].copy_from_slice(bytes);
+    }
+    Ok(())
+}
+
+// checks the state of the referenced account against a precondition
+fn check_precondition(accounts: &[AccountInfo<'_>], precondition: &Precondition) -> ProgramResult {
+    if !account_check(accounts, precondition.account, &precondition.check)? {
+        msg!(
+            "anyix precondition failed(account={})",
+            precondition.account
+        );
+        return Err(AnyIxError::PreconditionFailed.into());
+    }
+    Ok(())
+}
+
+// returns true if every guard of the instruction at `idx` holds
+fn guards_hold(
+    idx: usize,
+    accounts: &[AccountInfo<'_>],
+    extensions: &[Extension],
+) -> Result<bool, ProgramError> {
+    for extension in extensions.iter() {
+        let Extension::Guard(guard) = extension else {
+            continue;
+        };
+        if guard.instruction as usize == idx
+            && !account_check(accounts, guard.account, &guard.check)?
+        {
+            return Ok(false);
+        }
+    }
+    Ok(true)
+}
+
+// returns true if the current state of the referenced account satisfies `check`
+fn account_check(
+    accounts: &[AccountInfo<'_>],
+    account: u16,
+    check: &AccountCheck,
+) -> Result<bool, ProgramError> {
+    let account = accounts
+        .get(account as usize)
+        .ok_or(AnyIxError::AccountUnderflow)?;
+    Ok(match check {
+        AccountCheck::Owner(owner) => account.owner.eq(owner),
+        AccountCheck::Lamports(comparison, value) => comparison.compare(account.lamports(), *value),
+        AccountCheck::Data { offset, bytes } => {
+            let offset = *offset as usize;
+            account
+                .try_borrow_data()?
+                .get(offset..offset + bytes.len())
+                .is_some_and(|data| data.eq(&bytes[..]))
+        }
+        AccountCheck::DataHash(digest) => hash(&account.try_borrow_data()?).to_bytes().eq(digest),
+    })
+}
+
+// returns the balance an assertion applies to. a token account which has been
+// closed is treated as having a balance of zero
+fn balance_of(accounts: &[AccountInfo<'_>], assertion: &Assertion) -> Result<u64, ProgramError> {
+    let account = accounts
+        .get(assertion.account as usize)
+        .ok_or(AnyIxError::AccountUnderflow)?;
+    match assertion.balance {
+        Balance::Lamports => Ok(account.lamports()),
+        Balance::Token => {
+            if account.lamports() == 0 {
+                return Ok(0);
+            }
+            if !account.owner.eq(&SPL_TOKEN_PROGRAM_ID)
+                && !account.owner.eq(&SPL_TOKEN_2022_PROGRAM_ID)
+            {
+                return Err(AnyIxError::InvalidTokenAccount.into());
+            }
+            let data = account.try_borrow_data()?;
+            // mints are owned by the same programs, and must not be read as accounts
+            let is_account = match data.len() {
+                SPL_TOKEN_ACCOUNT_LEN => true,
+                len if len > SPL_TOKEN_ACCOUNT_LEN => {
+                    data[SPL_TOKEN_ACCOUNT_LEN] == SPL_TOKEN_2022_ACCOUNT_TYPE
+                }
+                _ => false,
+            };
+            if !is_account {
+                return Err(AnyIxError::InvalidTokenAccount.into());
+            }
+            let offset = SPL_TOKEN_AMOUNT_OFFSET as usize;
+            let mut bytes = [0u8; 8];
+            bytes.copy_from_slice(&data[offset..offset + 8]);
+            Ok(u64::from_le_bytes(bytes))
+        }
+    }
+}
+
+// returns the compute units left to the transaction, or 0 when events are
+// disabled, so that the syscall is only paid for, and solana-program 1.17 only
+// required, when it is needed
+#[cfg(feature = "events")]
+fn remaining_compute_units() -> u64 {
+    solana_program::compute_units::sol_remaining_compute_units()
+}
+
+#[cfg(not(feature = "events"))]
+fn remaining_compute_units() -> u64 {
+    0
+}
+
+fn log_event(event: &AnyIxEvent) {
+    sol_log_data(&[&event.pack()]);
+}
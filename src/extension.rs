@@ -0,0 +1,602 @@
+//! optional sections carried after the instruction datas of a v3 payload.
+//!
+//! the extension block is encoded as `[num_extensions: u8]` followed by each
+//! extension as `[kind: u8, len: u16, body: [u8; len]]`. unknown kinds are
+//! rejected rather than skipped, so an executor never silently ignores a
+//! section it does not understand
+
+use solana_program::pubkey::Pubkey;
+
+use crate::AnyIxError;
+
+/// extension kind for `Extension::Signer`
+pub const EXTENSION_SIGNER: u8 = 1;
+
+/// extension kind for `Extension::ReturnDataPatch`
+pub const EXTENSION_RETURN_DATA_PATCH: u8 = 2;
+
+/// extension kind for `Extension::AccountDataPatch`
+pub const EXTENSION_ACCOUNT_DATA_PATCH: u8 = 3;
+
+/// extension kind for `Extension::Assertion`
+pub const EXTENSION_ASSERTION: u8 = 4;
+
+/// extension kind for `Extension::Precondition`
+pub const EXTENSION_PRECONDITION: u8 = 5;
+
+/// extension kind for `Extension::Guard`
+pub const EXTENSION_GUARD: u8 = 6;
+
+/// extension kind for `Extension::Nonce`
+pub const EXTENSION_NONCE: u8 = 7;
+
+/// extension kind for `Extension::ValidUntil`
+pub const EXTENSION_VALID_UNTIL: u8 = 8;
+
+/// extension kind for `Extension::AccountCommitment`
+pub const EXTENSION_ACCOUNT_COMMITMENT: u8 = 9;
+
+/// extension kind for `Extension::AccountIndices`
+pub const EXTENSION_ACCOUNT_INDICES: u8 = 10;
+
+/// offset of the `amount` field within an spl token account
+pub const SPL_TOKEN_AMOUNT_OFFSET: u32 = 64;
+
+/// the size of an spl token account, token 2022 accounts with extensions being larger
+pub const SPL_TOKEN_ACCOUNT_LEN: usize = 165;
+
+/// the account type following the base account of a token 2022 account with
+/// extensions, distinguishing it from a mint
+pub const SPL_TOKEN_2022_ACCOUNT_TYPE: u8 = 2;
+
+/// the spl token program, whose accounts may be used in token balance assertions
+pub const SPL_TOKEN_PROGRAM_ID: Pubkey =
+    solana_program::pubkey!("TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA");
+
+/// the spl token 2022 program, whose accounts may be used in token balance assertions
+pub const SPL_TOKEN_2022_PROGRAM_ID: Pubkey =
+    solana_program::pubkey!("TokenzQdBNbLqP5VEhdkAS6EPFLC1PHnBqCXEpPxuEb");
+
+/// the maximum number of seeds, excluding the bump, in a signer extension
+pub const MAX_SIGNER_SEEDS: usize = solana_program::pubkey::MAX_SEEDS - 1;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Extension {
+    /// signs for a program derived address when invoking an instruction
+    Signer(SignerSeeds),
+    /// copies return data of one instruction into the data of a later instruction
+    ReturnDataPatch(ReturnDataPatch),
+    /// copies the data of an account into the data of an instruction
+    AccountDataPatch(AccountDataPatch),
+    /// checks the balance of an account once every instruction has been invoked
+    Assertion(Assertion),
+    /// checks the state of an account before any instruction is invoked
+    Precondition(Precondition),
+    /// skips an instruction unless the state of an account satisfies a check
+    Guard(Guard),
+    /// rejects the bundle unless the nonce matches that of a nonce account
+    Nonce(Nonce),
+    /// rejects the bundle once the clock has passed the slot
+    ValidUntil(u64),
+    /// rejects the bundle unless the hash of the accounts passed to the executor,
+    /// as computed by `hash::hash_account_infos`, matches
+    AccountCommitment([u8; 32]),
+    /// the index of every account of every instruction, each instruction's program
+    /// account first, into the accounts passed to the executor. when present, the
+    /// accounts are a deduplicated table rather than contiguous slices per instruction
+    AccountIndices(Vec<u8>),
+}
+
+/// seeds used to sign for a PDA of the executing program while invoking the
+/// instruction at index `instruction`.
+///
+/// executors require the first seed to equal a namespace chosen by the program,
+/// so that a bundle can only sign for PDAs the program has set aside for anyix
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SignerSeeds {
+    /// index of the instruction to sign for
+    pub instruction: u16,
+    /// the seeds, excluding the bump
+    pub seeds: Vec<Vec<u8>>,
+    pub bump: u8,
+}
+
+/// after the instruction at index `source` is invoked, copies `len` bytes at
+/// `source_offset` of its return data into the data of the later instruction at
+/// index `target`, starting at `target_offset`.
+///
+/// the return data must have been set by the program invoked by `source`, which
+/// allows for example a quote from one program to set the amount of a swap
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ReturnDataPatch {
+    pub source: u16,
+    pub target: u16,
+    pub source_offset: u16,
+    pub target_offset: u16,
+    pub len: u16,
+}
+
+/// immediately before the instruction at index `target` is invoked, copies `len`
+/// bytes at `source_offset` of the data of `account` into the instruction's data,
+/// starting at `target_offset`. `account` is the index of the account within the
+/// accounts passed to the executor.
+///
+/// this allows an instruction to act upon state changed by earlier instructions,
+/// for example transferring the entire balance of a token account
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct AccountDataPatch {
+    pub target: u16,
+    pub account: u16,
+    pub source_offset: u32,
+    pub target_offset: u16,
+    pub len: u16,
+}
+
+impl AccountDataPatch {
+    /// copies the `amount` of the spl token account at index `account` into the
+    /// data of the instruction at index `target`, as a little endian u64
+    pub fn token_amount(target: u16, account: u16, target_offset: u16) -> Self {
+        AccountDataPatch {
+            target,
+            account,
+            source_offset: SPL_TOKEN_AMOUNT_OFFSET,
+            target_offset,
+            len: 8,
+        }
+    }
+}
+
+/// a check of the balance of `account` after the last instruction is invoked,
+/// relative to its balance before the first instruction is invoked. `account` is
+/// the index of the account within the accounts passed to the executor.
+///
+/// assertions act as slippage guards, reverting the bundle if for example a swap
+/// route returned fewer tokens than expected
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Assertion {
+    pub account: u16,
+    pub balance: Balance,
+    pub check: BalanceCheck,
+}
+
+/// the balance an assertion applies to
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[repr(u8)]
+pub enum Balance {
+    /// the `amount` of an spl token account
+    Token = 0,
+    /// the lamports of any account
+    Lamports = 1,
+}
+
+/// how the balance of an assertion is checked
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BalanceCheck {
+    /// the final balance must be at least the value
+    Min(u64),
+    /// the balance must not decrease by more than the value
+    MaxDecrease(u64),
+    /// the balance must change by exactly the value
+    ExactDelta(i64),
+}
+
+impl BalanceCheck {
+    /// returns true if a balance changing from `before` to `after` satisfies the check
+    pub fn check(&self, before: u64, after: u64) -> bool {
+        match self {
+            BalanceCheck::Min(min) => after >= *min,
+            BalanceCheck::MaxDecrease(max) => before.saturating_sub(after) <= *max,
+            BalanceCheck::ExactDelta(delta) => after as i128 - before as i128 == *delta as i128,
+        }
+    }
+}
+
+/// a check of the state of `account` before the first instruction is invoked,
+/// aborting the bundle unless the state matches what the client observed when
+/// building it. `account` is the index of the account within the accounts passed
+/// to the executor
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Precondition {
+    pub account: u16,
+    pub check: AccountCheck,
+}
+
+/// a check of the state of `account` made immediately before the instruction at
+/// index `instruction` would be invoked, skipping the instruction if the check
+/// does not hold. an instruction with multiple guards is only invoked if all of
+/// them hold.
+///
+/// guards allow a bundle to be idempotent, such as only creating an associated
+/// token account if it is uninitialized, or only closing one with a zero balance
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Guard {
+    pub instruction: u16,
+    pub account: u16,
+    pub check: AccountCheck,
+}
+
+/// a nonce which must equal the nonce held by `account`, the index of a nonce
+/// account within the accounts passed to the executor. the nonce account is
+/// incremented before any instruction is invoked, so the bundle can not be
+/// executed twice
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Nonce {
+    pub account: u16,
+    /// the address of the nonce account, so that whoever submits the bundle can
+    /// not substitute another nonce account holding the same nonce
+    pub nonce_account: Pubkey,
+    pub nonce: u64,
+}
+
+/// the state checked by a precondition or guard
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum AccountCheck {
+    /// the account must be owned by the program
+    Owner(Pubkey),
+    /// the lamports of the account compared against the value must hold
+    Lamports(Comparison, u64),
+    /// the data of the account starting at `offset` must equal `bytes`
+    Data { offset: u32, bytes: Vec<u8> },
+    /// the sha256 hash of the data of the account must equal the digest
+    DataHash([u8; 32]),
+}
+
+/// how a value is compared against the expected value
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[repr(u8)]
+pub enum Comparison {
+    Eq = 0,
+    Ne = 1,
+    Lt = 2,
+    Le = 3,
+    Gt = 4,
+    Ge = 5,
+}
+
+impl Comparison {
+    /// returns true if `value` compared against `expected` holds
+    pub fn compare(&self, value: u64, expected: u64) -> bool {
+        match self {
+            Comparison::Eq => value == expected,
+            Comparison::Ne => value != expected,
+            Comparison::Lt => value < expected,
+            Comparison::Le => value <= expected,
+            Comparison::Gt => value > expected,
+            Comparison::Ge => value >= expected,
+        }
+    }
+}
+
+impl Extension {
+    /// returns the kind byte used to encode the extension
+    pub fn kind(&self) -> u8 {
+        match self {
+            Extension::Signer(_) => EXTENSION_SIGNER,
+            Extension::ReturnDataPatch(_) => EXTENSION_RETURN_DATA_PATCH,
+            Extension::AccountDataPatch(_) => EXTENSION_ACCOUNT_DATA_PATCH,
+            Extension::Assertion(_) => EXTENSION_ASSERTION,
+            Extension::Precondition(_) => EXTENSION_PRECONDITION,
+            Extension::Guard(_) => EXTENSION_GUARD,
+            Extension::Nonce(_) => EXTENSION_NONCE,
+            Extension::ValidUntil(_) => EXTENSION_VALID_UNTIL,
+            Extension::AccountCommitment(_) => EXTENSION_ACCOUNT_COMMITMENT,
+            Extension::AccountIndices(_) => EXTENSION_ACCOUNT_INDICES,
+        }
+    }
+    /// decodes the body of an extension of the given kind
+    pub fn unpack(kind: u8, body: &[u8]) -> Result<Self, AnyIxError> {
+        let mut reader = Reader(body);
+        let extension = match kind {
+            EXTENSION_SIGNER => {
+                let instruction = reader.read_u16()?;
+                let bump = reader.read_u8()?;
+                let num_seeds = reader.read_u8()? as usize;
+                if num_seeds > MAX_SIGNER_SEEDS {
+                    return Err(AnyIxError::InvalidExtension);
+                }
+                let mut seeds = Vec::with_capacity(num_seeds);
+                for _ in 0..num_seeds {
+                    let seed_len = reader.read_u8()? as usize;
+                    seeds.push(reader.read_bytes(seed_len)?.to_vec());
+                }
+                Extension::Signer(SignerSeeds {
+                    instruction,
+                    seeds,
+                    bump,
+                })
+            }
+            EXTENSION_RETURN_DATA_PATCH => Extension::ReturnDataPatch(ReturnDataPatch {
+                source: reader.read_u16()?,
+                target: reader.read_u16()?,
+                source_offset: reader.read_u16()?,
+                target_offset: reader.read_u16()?,
+                len: reader.read_u16()?,
+            }),
+            EXTENSION_ACCOUNT_DATA_PATCH => Extension::AccountDataPatch(AccountDataPatch {
+                target: reader.read_u16()?,
+                account: reader.read_u16()?,
+                source_offset: reader.read_u32()?,
+                target_offset: reader.read_u16()?,
+                len: reader.read_u16()?,
+            }),
+            EXTENSION_ASSERTION => {
+                let account = reader.read_u16()?;
+                let balance = match reader.read_u8()? {
+                    0 => Balance::Token,
+                    1 => Balance::Lamports,
+                    _ => return Err(AnyIxError::InvalidExtension),
+                };
+                let check = match reader.read_u8()? {
+                    0 => BalanceCheck::Min(reader.read_u64()?),
+                    1 => BalanceCheck::MaxDecrease(reader.read_u64()?),
+                    2 => BalanceCheck::ExactDelta(reader.read_u64()? as i64),
+                    _ => return Err(AnyIxError::InvalidExtension),
+                };
+                Extension::Assertion(Assertion {
+                    account,
+                    balance,
+                    check,
+                })
+            }
+            EXTENSION_PRECONDITION => Extension::Precondition(Precondition {
+                account: reader.read_u16()?,
+                check: reader.read_account_check()?,
+            }),
+            EXTENSION_GUARD => Extension::Guard(Guard {
+                instruction: reader.read_u16()?,
+                account: reader.read_u16()?,
+                check: reader.read_account_check()?,
+            }),
+            EXTENSION_NONCE => Extension::Nonce(Nonce {
+                account: reader.read_u16()?,
+                nonce_account: Pubkey::new_from_array(reader.read_array()?),
+                nonce: reader.read_u64()?,
+            }),
+            EXTENSION_VALID_UNTIL => Extension::ValidUntil(reader.read_u64()?),
+            EXTENSION_ACCOUNT_COMMITMENT => Extension::AccountCommitment(reader.read_array()?),
+            EXTENSION_ACCOUNT_INDICES => {
+                Extension::AccountIndices(reader.read_bytes(reader.0.len())?.to_vec())
+            }
+            _ => return Err(AnyIxError::InvalidExtension),
+        };
+        if !reader.0.is_empty() {
+            return Err(AnyIxError::InvalidExtension);
+        }
+        Ok(extension)
+    }
+    /// encodes the extension, including the kind and length prefix
+    pub fn pack_into(&self, out: &mut Vec<u8>) -> Result<(), AnyIxError> {
+        let mut body = Vec::new();
+        match self {
+            Extension::Signer(signer) => {
+                if signer.seeds.len() > MAX_SIGNER_SEEDS {
+                    return Err(AnyIxError::LengthOverflow);
+                }
+                body.extend_from_slice(&signer.instruction.to_le_bytes());
+                body.push(signer.bump);
+                body.push(signer.seeds.len() as u8);
+                for seed in signer.seeds.iter() {
+                    if seed.len() > solana_program::pubkey::MAX_SEED_LEN {
+                        return Err(AnyIxError::LengthOverflow);
+                    }
+                    body.push(seed.len() as u8);
+                    body.extend_from_slice(seed);
+                }
+            }
+            Extension::ReturnDataPatch(patch) => {
+                for value in [
+                    patch.source,
+                    patch.target,
+                    patch.source_offset,
+                    patch.target_offset,
+                    patch.len,
+                ] {
+                    body.extend_from_slice(&value.to_le_bytes());
+                }
+            }
+            Extension::AccountDataPatch(patch) => {
+                body.extend_from_slice(&patch.target.to_le_bytes());
+                body.extend_from_slice(&patch.account.to_le_bytes());
+                body.extend_from_slice(&patch.source_offset.to_le_bytes());
+                body.extend_from_slice(&patch.target_offset.to_le_bytes());
+                body.extend_from_slice(&patch.len.to_le_bytes());
+            }
+            Extension::Assertion(assertion) => {
+                body.extend_from_slice(&assertion.account.to_le_bytes());
+                body.push(assertion.balance as u8);
+                let (check, value) = match assertion.check {
+                    BalanceCheck::Min(min) => (0, min),
+                    BalanceCheck::MaxDecrease(max) => (1, max),
+                    BalanceCheck::ExactDelta(delta) => (2, delta as u64),
+                };
+                body.push(check);
+                body.extend_from_slice(&value.to_le_bytes());
+            }
+            Extension::Precondition(precondition) => {
+                body.extend_from_slice(&precondition.account.to_le_bytes());
+                precondition.check.pack_into(&mut body)?;
+            }
+            Extension::Guard(guard) => {
+                body.extend_from_slice(&guard.instruction.to_le_bytes());
+                body.extend_from_slice(&guard.account.to_le_bytes());
+                guard.check.pack_into(&mut body)?;
+            }
+            Extension::Nonce(nonce) => {
+                body.extend_from_slice(&nonce.account.to_le_bytes());
+                body.extend_from_slice(nonce.nonce_account.as_ref());
+                body.extend_from_slice(&nonce.nonce.to_le_bytes());
+            }
+            Extension::ValidUntil(slot) => body.extend_from_slice(&slot.to_le_bytes()),
+            Extension::AccountCommitment(commitment) => body.extend_from_slice(commitment),
+            Extension::AccountIndices(indices) => body.extend_from_slice(indices),
+        }
+        let body_len = u16::try_from(body.len()).map_err(|_| AnyIxError::LengthOverflow)?;
+        out.push(self.kind());
+        out.extend_from_slice(&body_len.to_le_bytes());
+        out.extend_from_slice(&body);
+        Ok(())
+    }
+    /// returns the index of the account the extension reads or writes, if any, into
+    /// the accounts passed to the executor
+    pub fn account(&self) -> Option<u16> {
+        match self {
+            Extension::AccountDataPatch(patch) => Some(patch.account),
+            Extension::Assertion(assertion) => Some(assertion.account),
+            Extension::Precondition(precondition) => Some(precondition.account),
+            Extension::Guard(guard) => Some(guard.account),
+            Extension::Nonce(nonce) => Some(nonce.account),
+            Extension::Signer(_)
+            | Extension::ReturnDataPatch(_)
+            | Extension::ValidUntil(_)
+            | Extension::AccountCommitment(_)
+            | Extension::AccountIndices(_) => None,
+        }
+    }
+    /// returns the index of the instruction the extension applies to, if any
+    pub fn instruction(&self) -> Option<u16> {
+        match self {
+            Extension::Signer(signer) => Some(signer.instruction),
+            Extension::ReturnDataPatch(patch) => Some(patch.target),
+            Extension::AccountDataPatch(patch) => Some(patch.target),
+            Extension::Guard(guard) => Some(guard.instruction),
+            Extension::Assertion(_)
+            | Extension::Precondition(_)
+            | Extension::Nonce(_)
+            | Extension::ValidUntil(_)
+            | Extension::AccountCommitment(_)
+            | Extension::AccountIndices(_) => None,
+        }
+    }
+}
+
+impl AccountCheck {
+    fn pack_into(&self, body: &mut Vec<u8>) -> Result<(), AnyIxError> {
+        match self {
+            AccountCheck::Owner(owner) => {
+                body.push(0);
+                body.extend_from_slice(owner.as_ref());
+            }
+            AccountCheck::Lamports(comparison, value) => {
+                body.push(1);
+                body.push(*comparison as u8);
+                body.extend_from_slice(&value.to_le_bytes());
+            }
+            AccountCheck::Data { offset, bytes } => {
+                let len = u16::try_from(bytes.len()).map_err(|_| AnyIxError::LengthOverflow)?;
+                body.push(2);
+                body.extend_from_slice(&offset.to_le_bytes());
+                body.extend_from_slice(&len.to_le_bytes());
+                body.extend_from_slice(bytes);
+            }
+            AccountCheck::DataHash(digest) => {
+                body.push(3);
+                body.extend_from_slice(digest);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// iterator over the extensions of a payload, decoding each one as it is reached
+#[derive(Clone, Debug)]
+pub struct ExtensionIter<'a> {
+    pub(crate) remaining: u8,
+    pub(crate) data: &'a [u8],
+}
+
+impl<'a> ExtensionIter<'a> {
+    /// validates the framing of an extension block, returning an iterator over it
+    pub(crate) fn new(block: &'a [u8]) -> Result<Self, AnyIxError> {
+        let mut reader = Reader(block);
+        let num_extensions = reader.read_u8()?;
+        let data = reader.0;
+        for _ in 0..num_extensions {
+            reader.read_u8()?;
+            let body_len = reader.read_u16()? as usize;
+            reader.read_bytes(body_len)?;
+        }
+        if !reader.0.is_empty() {
+            return Err(AnyIxError::TrailingBytes);
+        }
+        Ok(ExtensionIter {
+            remaining: num_extensions,
+            data,
+        })
+    }
+}
+
+impl Iterator for ExtensionIter<'_> {
+    type Item = Result<Extension, AnyIxError>;
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.remaining == 0 {
+            return None;
+        }
+        self.remaining -= 1;
+        // framing was validated in `new`, so reading the header can not fail
+        let kind = self.data[0];
+        let body_len = u16::from_le_bytes([self.data[1], self.data[2]]) as usize;
+        let body = &self.data[3..3 + body_len];
+        self.data = &self.data[3 + body_len..];
+        Some(Extension::unpack(kind, body))
+    }
+}
+
+// minimal cursor used to decode extension bodies
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn read_bytes(&mut self, count: usize) -> Result<&'a [u8], AnyIxError> {
+        if self.0.len() < count {
+            return Err(AnyIxError::InvalidExtension);
+        }
+        let (bytes, rest) = self.0.split_at(count);
+        self.0 = rest;
+        Ok(bytes)
+    }
+    fn read_u8(&mut self) -> Result<u8, AnyIxError> {
+        Ok(self.read_bytes(1)?[0])
+    }
+    fn read_u16(&mut self) -> Result<u16, AnyIxError> {
+        let bytes = self.read_bytes(2)?;
+        Ok(u16::from_le_bytes([bytes[0], bytes[1]]))
+    }
+    fn read_u32(&mut self) -> Result<u32, AnyIxError> {
+        let bytes = self.read_bytes(4)?;
+        Ok(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+    fn read_u64(&mut self) -> Result<u64, AnyIxError> {
+        Ok(u64::from_le_bytes(self.read_array()?))
+    }
+    fn read_account_check(&mut self) -> Result<AccountCheck, AnyIxError> {
+        Ok(match self.read_u8()? {
+            0 => AccountCheck::Owner(Pubkey::new_from_array(self.read_array()?)),
+            1 => {
+                let comparison = match self.read_u8()? {
+                    0 => Comparison::Eq,
+                    1 => Comparison::Ne,
+                    2 => Comparison::Lt,
+                    3 => Comparison::Le,
+                    4 => Comparison::Gt,
+                    5 => Comparison::Ge,
+                    _ => return Err(AnyIxError::InvalidExtension),
+                };
+                AccountCheck::Lamports(comparison, self.read_u64()?)
+            }
+            2 => {
+                let offset = self.read_u32()?;
+                let len = self.read_u16()? as usize;
+                AccountCheck::Data {
+                    offset,
+                    bytes: self.read_bytes(len)?.to_vec(),
+                }
+            }
+            3 => AccountCheck::DataHash(self.read_array()?),
+            _ => return Err(AnyIxError::InvalidExtension),
+        })
+    }
+    fn read_array<const N: usize>(&mut self) -> Result<[u8; N], AnyIxError> {
+        let mut bytes = [0u8; N];
+        bytes.copy_from_slice(self.read_bytes(N)?);
+        Ok(bytes)
+    }
+}
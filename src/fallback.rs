@@ -0,0 +1,103 @@
+//! routes the instructions of a program either to `handle_anyix` or to the
+//! program's own processor, depending on whether the instruction data starts
+//! with a reserved discriminator. `anyix_fallback!` generates the entrypoint's
+//! process function, for example
+//!
+//! ```ignore
+//! anyix::anyix_fallback!(process_instruction, processor::process, AllowAll);
+//! solana_program::entrypoint!(process_instruction);
+//! ```
+//!
+//! by default the discriminator is `ANYIX_DISCRIMINATOR`, the anchor sighash of
+//! an instruction named `anyix`, so that it does not collide with the instructions
+//! of an anchor program
+
+use solana_program::account_info::AccountInfo;
+use solana_program::entrypoint::ProgramResult;
+use solana_program::hash::hash;
+use solana_program::pubkey::Pubkey;
+
+use crate::{handle_anyix, AnyIxPolicy};
+
+/// the default discriminator, `sighash("global", "anyix")`
+pub const ANYIX_DISCRIMINATOR: [u8; 8] = [166, 171, 89, 235, 212, 202, 166, 188];
+
+/// returns the anchor style discriminator of an instruction, the first 8 bytes of
+/// the sha256 hash of `namespace:name`
+pub fn sighash(namespace: &str, name: &str) -> [u8; 8] {
+    let mut discriminator = [0u8; 8];
+    discriminator.copy_from_slice(&hash(format!("{namespace}:{name}").as_bytes()).to_bytes()[..8]);
+    discriminator
+}
+
+/// returns the instruction data routing a packed bundle to `handle_anyix`
+pub fn fallback_data(discriminator: &[u8], packed: &[u8]) -> Vec<u8> {
+    [discriminator, packed].concat()
+}
+
+/// executes the bundle following `discriminator` with `handle_anyix` if `data`
+/// starts with it, otherwise passes the instruction to `processor` unchanged.
+/// an empty discriminator never matches
+pub fn process_fallback<'info>(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo<'info>],
+    data: &[u8],
+    discriminator: &[u8],
+    policy: &dyn AnyIxPolicy,
+    processor: impl FnOnce(&Pubkey, &[AccountInfo<'info>], &[u8]) -> ProgramResult,
+) -> ProgramResult {
+    match data.strip_prefix(discriminator) {
+        Some(packed) if !discriminator.is_empty() => {
+            handle_anyix(*program_id, accounts, packed, policy)
+        }
+        _ => processor(program_id, accounts, data),
+    }
+}
+
+/// defines a process function named `$name`, suitable for `entrypoint!`, which
+/// routes instructions with `process_fallback`. the discriminator defaults to
+/// `ANYIX_DISCRIMINATOR`
+///
+/// ```ignore
+/// anyix_fallback!(process_instruction, processor::process, ProgramAllowlist(&[spl_token::ID]));
+/// anyix_fallback!(
+///     process_instruction,
+///     processor::process,
+///     AllowAll,
+///     discriminator = [0xff]
+/// );
+/// ```
+#[macro_export]
+macro_rules! anyix_fallback {
+    ($name:ident, $processor:path, $policy:expr) => {
+        $crate::anyix_fallback!(
+            $name,
+            $processor,
+            $policy,
+            discriminator = $crate::fallback::ANYIX_DISCRIMINATOR
+        );
+    };
+    ($name:ident, $processor:path, $policy:expr, discriminator = $discriminator:expr) => {
+        pub fn $name(
+            program_id: &$crate::fallback::__private::Pubkey,
+            accounts: &[$crate::fallback::__private::AccountInfo],
+            data: &[u8],
+        ) -> $crate::fallback::__private::ProgramResult {
+            $crate::fallback::process_fallback(
+                program_id,
+                accounts,
+                data,
+                &$discriminator,
+                &$policy,
+                $processor,
+            )
+        }
+    };
+}
+
+#[doc(hidden)]
+pub mod __private {
+    pub use solana_program::account_info::AccountInfo;
+    pub use solana_program::entrypoint::ProgramResult;
+    pub use solana_program::pubkey::Pubkey;
+}
@@ -0,0 +1,57 @@
+//! deterministic ids for bundles, shared by clients, indexers and on-chain code.
+//!
+//! the id of a bundle is the sha256 hash of the hash of its canonical v2 or v3
+//! encoding, followed by the hash of the accounts it is executed with. every
+//! account contributes its pubkey followed by a flags byte, so a bundle executed
+//! with the same data but different or reordered accounts has a different id
+
+use solana_program::account_info::AccountInfo;
+use solana_program::hash::{hash, hashv};
+use solana_program::instruction::AccountMeta;
+use solana_program::pubkey::Pubkey;
+
+/// set in the flags byte of an account which is a signer
+pub const ACCOUNT_FLAG_SIGNER: u8 = 1;
+
+/// set in the flags byte of an account which is writable
+pub const ACCOUNT_FLAG_WRITABLE: u8 = 2;
+
+/// returns the hash of the accounts passed to `handle_anyix`, as built by a client
+pub fn hash_account_metas(accounts: &[AccountMeta]) -> [u8; 32] {
+    hash_accounts(
+        accounts
+            .iter()
+            .map(|meta| (&meta.pubkey, meta.is_signer, meta.is_writable)),
+    )
+}
+
+/// returns the hash of the accounts passed to `handle_anyix`, as seen on-chain
+pub fn hash_account_infos(accounts: &[AccountInfo<'_>]) -> [u8; 32] {
+    hash_accounts(
+        accounts
+            .iter()
+            .map(|account| (account.key, account.is_signer, account.is_writable)),
+    )
+}
+
+/// returns the id of a bundle, given the hash of its canonical encoding and the
+/// hash of its accounts
+pub fn bundle_id(anyix_hash: &[u8; 32], accounts_hash: &[u8; 32]) -> [u8; 32] {
+    hashv(&[anyix_hash, accounts_hash]).to_bytes()
+}
+
+fn hash_accounts<'a>(accounts: impl Iterator<Item = (&'a Pubkey, bool, bool)>) -> [u8; 32] {
+    let mut data = Vec::with_capacity(accounts.size_hint().0 * 33);
+    for (pubkey, is_signer, is_writable) in accounts {
+        let mut flags = 0;
+        if is_signer {
+            flags |= ACCOUNT_FLAG_SIGNER;
+        }
+        if is_writable {
+            flags |= ACCOUNT_FLAG_WRITABLE;
+        }
+        data.extend_from_slice(pubkey.as_ref());
+        data.push(flags);
+    }
+    hash(&data).to_bytes()
+}
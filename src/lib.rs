@@ -1,188 +1,353 @@
+#[cfg(feature = "anchor")]
+pub mod anchor;
+// the accounts owned by a program on behalf of anyix start with a tag, unique
+// to each kind of account, so that one can not be passed in place of another:
+// `BUFFER_INITIALIZED` (1), `CONFIG_INITIALIZED` (2) and `NONCE_INITIALIZED` (3).
+// a tag of 0 marks an account which has not been initialized
+pub mod buffer;
+pub mod builder;
+pub mod config;
+pub mod ed25519;
+pub mod error;
+pub mod event;
+mod executor;
+pub mod extension;
+pub mod fallback;
+pub mod hash;
+pub mod nonce;
+pub mod policy;
+pub mod view;
+
 use std::fmt::Debug;
 
+pub use buffer::{
+    handle_close_buffer, handle_execute_buffer, handle_execute_next, handle_init_buffer,
+    handle_init_buffer_with_expiry, handle_write_buffer, BufferHeader,
+};
+pub use builder::AnyIxBuilder;
+pub use config::{
+    config_address, handle_anyix_signed_with_config, handle_anyix_with_config, handle_init_config,
+    handle_rotate_authority, handle_set_authorities, AnyIxConfig,
+};
+pub use ed25519::{bundle_message, ed25519_instruction, handle_anyix_ed25519};
+pub use error::AnyIxError;
+#[cfg(feature = "client")]
+pub use event::decode_events;
+pub use event::{AnyIxEvent, InstructionEvent, SummaryEvent};
+use executor::Executor;
+pub use extension::{
+    AccountCheck, AccountDataPatch, Assertion, Balance, BalanceCheck, Comparison, Extension, Guard,
+    Nonce, Precondition, ReturnDataPatch, SignerSeeds,
+};
+pub use fallback::{fallback_data, process_fallback, sighash, ANYIX_DISCRIMINATOR};
+pub use nonce::handle_init_nonce;
+pub use policy::{AllowAll, AnyIxPolicy, ProgramAllowlist, ProgramDenylist};
 use solana_program::instruction::AccountMeta;
 use solana_program::instruction::Instruction;
-use solana_program::program_error::ProgramError;
 use solana_program::pubkey::Pubkey;
 use solana_program::{account_info::AccountInfo, entrypoint::ProgramResult};
+pub use view::{AnyIxIter, AnyIxRef};
 
 /// Helper function to handle unpacking instruction data
-/// and executing the contained instructions. At the moment
-/// this function is not capable of handling signed CPI invocation
-/// and will carry through signing permissions from the transaction itself
+/// and executing the contained instructions. This function
+/// rejects bundles requesting signed CPI invocation, see `handle_anyix_signed_with_config`,
+/// and will carry through signing permissions from the transaction itself.
+///
+/// every inner instruction must be allowed by `policy`
 pub fn handle_anyix<'info>(
     program_id: Pubkey,
     accounts: &[AccountInfo<'info>],
     data: &[u8],
+    policy: &dyn AnyIxPolicy,
 ) -> ProgramResult {
-    solana_program::msg!("total accounts {}", accounts.len());
-    let arb_ix = AnyIx::unpack(data).unwrap();
-    let AnyIx {
-        num_instructions,
-        instruction_data_sizes: _,
-        instruction_datas,
-        instruction_account_counts,
-    } = arb_ix;
-    let mut offset = 0;
-    for idx in 0..num_instructions {
-        use solana_program::msg;
-        let cpi_accounts =
-            &accounts[offset as usize..instruction_account_counts[idx as usize] as usize + offset as usize];
-        offset += instruction_account_counts[idx as usize];
-        let program_account = &cpi_accounts[0];
-        if program_id.eq(program_account.key) {
-            panic!("self invocation not allowed");
-        }
-        msg!("processing anyix(idx={}, num_accounts={}, offset={})", idx, cpi_accounts.len(), offset);
-        solana_program::program::invoke(
-            &Instruction {
-                program_id: *program_account.key,
-                accounts: cpi_accounts[1..]
-                    .iter()
-                    .map(|account| {
-                        if account.is_writable {
-                            AccountMeta::new(*account.key, account.is_signer)
-                        } else {
-                            AccountMeta::new_readonly(*account.key, account.is_signer)
-                        }
-                    })
-                    .collect(),
-                data: instruction_datas[idx as usize].clone(),
-            },
-            &cpi_accounts[1..],
-        )?;
+    Executor::new(&program_id, policy).execute(accounts, &AnyIxRef::unpack(data)?)
+}
+
+/// Same as `handle_anyix`, for programs which pass their own fixed accounts, such as
+/// a config and an authority, ahead of the bundle's accounts. the bundle is executed
+/// with `accounts[offset..]`, see `handle_anyix_remaining`
+pub fn handle_anyix_at<'info>(
+    program_id: Pubkey,
+    accounts: &[AccountInfo<'info>],
+    offset: usize,
+    data: &[u8],
+    policy: &dyn AnyIxPolicy,
+) -> ProgramResult {
+    let remaining_accounts = accounts.get(offset..).ok_or(AnyIxError::AccountUnderflow)?;
+    handle_anyix_remaining(program_id, remaining_accounts, data, policy)
+}
+
+/// Same as `handle_anyix`, but executes the bundle with `remaining_accounts`, such as
+/// the remaining accounts of an anchor context, which the bundle must use exactly.
+/// accounts which are neither used by an instruction nor referenced by an extension
+/// are rejected
+pub fn handle_anyix_remaining<'info>(
+    program_id: Pubkey,
+    remaining_accounts: &[AccountInfo<'info>],
+    data: &[u8],
+    policy: &dyn AnyIxPolicy,
+) -> ProgramResult {
+    Executor::new(&program_id, policy)
+        .with_exact_accounts()
+        .execute(remaining_accounts, &AnyIxRef::unpack(data)?)
+}
+
+// same as `handle_anyix`, but additionally allows the bundle to sign for PDAs of
+// `program_id` within `signer_namespace`. nothing ties the signing to an
+// authority, so callers must have authorized the bundle, see
+// `handle_anyix_signed_with_config`
+pub(crate) fn handle_anyix_signed<'info>(
+    program_id: Pubkey,
+    accounts: &[AccountInfo<'info>],
+    data: &[u8],
+    signer_namespace: &[u8],
+    policy: &dyn AnyIxPolicy,
+) -> ProgramResult {
+    if signer_namespace.is_empty() {
+        return Err(AnyIxError::SignerNamespace.into());
     }
-    Ok(())
+    Executor::new(&program_id, policy)
+        .with_signer_namespace(signer_namespace)
+        .execute(accounts, &AnyIxRef::unpack(data)?)
 }
 
-/// this is an unsafe versio nof handle_anyix, which bypasses a variety of protection
-/// features
+/// this is an unsafe version of handle_anyix, which bypasses a variety of protection
+/// features, and is only available with the `unsafe-exec` feature.
+///
+/// accounts are sliced exactly as in `handle_anyix`, with each instruction's
+/// program account preceding its own accounts, however:
+///
+/// * no policy is consulted, any program may be invoked with any signer
+/// * an inner instruction may invoke `program_id` itself, re-entering the
+///   calling program with whatever accounts and data the bundle supplies
+///
+/// signed invocation is not supported
+#[cfg(feature = "unsafe-exec")]
 pub fn handle_anyix_unsafe<'info>(
     program_id: Pubkey,
     accounts: &[AccountInfo<'info>],
     data: &[u8],
 ) -> ProgramResult {
-    let arb_ix = AnyIx::unpack(data).unwrap();
-    let AnyIx {
-        num_instructions,
-        instruction_data_sizes: _,
-        instruction_datas,
-        instruction_account_counts,
-    } = arb_ix;
+    Executor::new(&program_id, &AllowAll)
+        .with_self_invocation()
+        .execute(accounts, &AnyIxRef::unpack(data)?)
+}
+
+/// encodes a set of instructions into the AnyIx format, with each account
+/// count including the program account that `handle_anyix` expects to precede
+/// the instruction's accounts. see `AnyIxBuilder` for building the accounts
+pub fn encode_instructions(ixs: &[Instruction]) -> Result<AnyIx, AnyIxError> {
+    AnyIxBuilder::new()
+        .add_instructions(ixs.iter().cloned())
+        .anyix()
+}
+
+/// decodes a packed AnyIx payload back into the instructions it contains, the
+/// inverse of `AnyIxBuilder::build`. `accounts` are the accounts the payload was
+/// executed with, sliced the same way as `handle_anyix` with each instruction's
+/// program account preceding its own accounts, or resolved through the account
+/// indices extension if the payload has one
+pub fn decode_instructions(
+    data: &[u8],
+    accounts: &[AccountMeta],
+) -> Result<Vec<Instruction>, AnyIxError> {
+    AnyIxRef::unpack(data)?.instructions(accounts)
+}
+
+// rebuilds instructions from `(account_count, data)` entries, slicing `accounts`
+// or resolving them through `indices`, with the program account first
+pub(crate) fn to_instructions<'a>(
+    entries: impl Iterator<Item = (u16, &'a [u8])>,
+    accounts: &[AccountMeta],
+    indices: Option<&[u8]>,
+) -> Result<Vec<Instruction>, AnyIxError> {
     let mut offset = 0;
-    for idx in 0..num_instructions {
-        let accounts =
-            &accounts[offset as usize..instruction_account_counts[idx as usize] as usize];
-        offset += instruction_account_counts[idx as usize];
-        solana_program::program::invoke(
-            &Instruction {
-                program_id,
-                accounts: accounts
+    entries
+        .map(|(account_count, ix_data)| {
+            let range = offset..offset + account_count as usize;
+            offset += account_count as usize;
+            let ix_accounts = match indices {
+                Some(indices) => indices
+                    .get(range)
+                    .ok_or(AnyIxError::InvalidExtension)?
                     .iter()
-                    .map(|account| {
-                        if account.is_writable {
-                            AccountMeta::new(*account.key, account.is_signer)
-                        } else {
-                            AccountMeta::new_readonly(*account.key, account.is_signer)
-                        }
-                    })
-                    .collect(),
-                data: instruction_datas[idx as usize].clone(),
-            },
-            accounts,
-        )?;
-    }
-    Ok(())
+                    .map(|idx| accounts.get(*idx as usize).cloned())
+                    .collect::<Option<Vec<_>>>(),
+                None => accounts.get(range).map(|accounts| accounts.to_vec()),
+            }
+            .ok_or(AnyIxError::AccountUnderflow)?;
+            let (program_account, ix_accounts) = ix_accounts
+                .split_first()
+                .ok_or(AnyIxError::AccountUnderflow)?;
+            Ok(Instruction {
+                program_id: program_account.pubkey,
+                accounts: ix_accounts.to_vec(),
+                data: ix_data.to_vec(),
+            })
+        })
+        .collect()
 }
 
-/// encodes a set of instructions into the AnyIx format
-pub fn encode_instructions(ixs: &[Instruction]) -> AnyIx {
-    let num_instructions = ixs.len();
-
-    let ix_data_sizes: Vec<u8> = ixs
-        .iter()
-        .map(|ix| ix.data.len().try_into().unwrap())
-        .collect();
-    let ix_datas = ixs.iter().map(|ix| ix.data.clone()).collect::<Vec<_>>();
-    let ix_account_counts: Vec<u8> = ixs
-        .iter()
-        .map(|ix| ix.accounts.len().try_into().unwrap())
-        .collect::<Vec<_>>();
-    AnyIx {
-        num_instructions: num_instructions as u8,
-        instruction_account_counts: ix_account_counts,
-        instruction_data_sizes: ix_data_sizes,
-        instruction_datas: ix_datas,
-    }
-}
+/// leading byte of a versioned payload. in the v1 format the first byte is the
+/// instruction count, so a zero here would denote an empty bundle; versioned
+/// payloads use it as a tag, followed by the format version
+pub const VERSIONED_TAG: u8 = 0;
 
-#[derive(Clone, PartialEq, Eq)]
+/// the v2 format, which uses little endian u16 values for the instruction count,
+/// the instruction data sizes and the instruction account counts
+///
+/// `[VERSIONED_TAG, 2, num_instructions: u16, data_sizes: [u16; n], account_counts: [u16; n], datas...]`
+pub const VERSION_2: u8 = 2;
+
+/// the v3 format, which is the v2 format followed by a block of extensions, see
+/// the `extension` module. payloads without extensions are encoded as v2
+pub const VERSION_3: u8 = 3;
+
+#[derive(Clone, Default, PartialEq, Eq)]
 pub struct AnyIx {
     /// the total number of individual instructions
-    pub num_instructions: u8,
-    pub instruction_data_sizes: Vec<u8>,
+    pub num_instructions: u16,
+    pub instruction_data_sizes: Vec<u16>,
     /// the number of accounts to use for a single instruction
     /// for example of this field is set to vec![10, 5], then the first instruction
     /// uses 10 accounts, with the second instruction using 5 accounts
-    pub instruction_account_counts: Vec<u8>,
+    pub instruction_account_counts: Vec<u16>,
     /// a vector of vectors, where each element is the instruction data
     /// to pass for instruction_datas[N]
     pub instruction_datas: Vec<Vec<u8>>,
+    /// optional sections such as signer seeds, which require the v3 format
+    pub extensions: Vec<Extension>,
 }
 
 impl AnyIx {
-    pub fn unpack(input: &[u8]) -> Result<Self, ProgramError> {
-        if input.is_empty() {
-            return Err(ProgramError::InvalidInstructionData);
-        }
-
-        let (num_instructions, data) = AnyIx::unpack_u8_slice(&input[0..], 1)?;
-        let (instruction_data_sizes, data) =
-            AnyIx::unpack_u8_slice(data, num_instructions[0] as usize)?;
-        let (instruction_account_counts, mut data) =
-            AnyIx::unpack_u8_slice(data, num_instructions[0] as usize)?;
-        let mut instruction_datas = Vec::with_capacity(30);
-        for data_size in instruction_data_sizes {
-            let (ix_data, data2) = data.split_at(*data_size as usize);
-            data = data2;
-            instruction_datas.push(ix_data.to_vec());
-        }
-        Ok(AnyIx {
-            num_instructions: num_instructions[0],
-            instruction_data_sizes: instruction_data_sizes.to_vec(),
-            instruction_account_counts: instruction_account_counts.to_vec(),
-            instruction_datas: instruction_datas.to_vec(),
-        })
+    /// decodes a v3 or v2 payload, or a legacy v1 payload which uses u8
+    /// instruction counts, data sizes and account counts
+    pub fn unpack(input: &[u8]) -> Result<Self, AnyIxError> {
+        AnyIxRef::unpack(input)?.to_owned()
     }
-    pub fn pack(&self) -> Result<Vec<u8>, ProgramError> {
+    /// decodes the bundle back into its instructions, see `decode_instructions`
+    pub fn instructions(&self, accounts: &[AccountMeta]) -> Result<Vec<Instruction>, AnyIxError> {
+        self.validate_lengths()?;
+        to_instructions(
+            self.instruction_account_counts
+                .iter()
+                .copied()
+                .zip(self.instruction_datas.iter().map(|data| &data[..])),
+            accounts,
+            self.extensions
+                .iter()
+                .find_map(|extension| match extension {
+                    Extension::AccountIndices(indices) => Some(&indices[..]),
+                    _ => None,
+                }),
+        )
+    }
+    /// encodes the instructions using the v2 format, or the v3 format if the
+    /// bundle has any extensions
+    pub fn pack(&self) -> Result<Vec<u8>, AnyIxError> {
+        self.validate_lengths()?;
+        let data_len: usize = self.instruction_datas.iter().map(|data| data.len()).sum();
+        let mut datas = Vec::with_capacity(4 + self.instruction_data_sizes.len() * 4 + data_len);
+        datas.push(VERSIONED_TAG);
+        datas.push(if self.extensions.is_empty() {
+            VERSION_2
+        } else {
+            VERSION_3
+        });
+        datas.extend_from_slice(&self.num_instructions.to_le_bytes());
+        for data_size in self.instruction_data_sizes.iter() {
+            datas.extend_from_slice(&data_size.to_le_bytes());
+        }
+        for account_count in self.instruction_account_counts.iter() {
+            datas.extend_from_slice(&account_count.to_le_bytes());
+        }
+        for ix_data in self.instruction_datas.iter() {
+            datas.extend_from_slice(ix_data);
+        }
+        if !self.extensions.is_empty() {
+            datas
+                .push(u8::try_from(self.extensions.len()).map_err(|_| AnyIxError::LengthOverflow)?);
+            for extension in self.extensions.iter() {
+                extension.pack_into(&mut datas)?;
+            }
+        }
+        Ok(datas)
+    }
+    /// returns the sha256 hash of the canonical encoding produced by `pack`
+    pub fn hash(&self) -> Result<[u8; 32], AnyIxError> {
+        Ok(solana_program::hash::hash(&self.pack()?).to_bytes())
+    }
+    /// returns the id of the bundle when executed with `accounts`, covering the
+    /// pubkeys and flags of the accounts as well as the encoding, see `hash::bundle_id`
+    pub fn id(&self, accounts: &[AccountMeta]) -> Result<[u8; 32], AnyIxError> {
+        Ok(hash::bundle_id(
+            &self.hash()?,
+            &hash::hash_account_metas(accounts),
+        ))
+    }
+    /// encodes the instructions using the legacy v1 format, for use with programs
+    /// that have not been upgraded to understand v2 payloads. returns an error if
+    /// any of the counts or sizes do not fit within a u8, or if the bundle has
+    /// extensions
+    pub fn pack_v1(&self) -> Result<Vec<u8>, AnyIxError> {
+        self.validate_lengths()?;
+        if !self.extensions.is_empty() {
+            return Err(AnyIxError::InvalidExtension);
+        }
+        let to_u8 = |value: u16| u8::try_from(value).map_err(|_| AnyIxError::LengthOverflow);
         let mut datas = Vec::with_capacity(std::mem::size_of_val(self));
-        datas.push(self.num_instructions);
-        datas.extend_from_slice(&self.instruction_data_sizes[..]);
-        datas.extend_from_slice(&self.instruction_account_counts[..]);
+        datas.push(to_u8(self.num_instructions)?);
+        for data_size in self.instruction_data_sizes.iter() {
+            datas.push(to_u8(*data_size)?);
+        }
+        for account_count in self.instruction_account_counts.iter() {
+            datas.push(to_u8(*account_count)?);
+        }
         for ix_data in self.instruction_datas.iter() {
             datas.extend_from_slice(ix_data);
         }
         Ok(datas)
     }
-    // returns a slice of of `count` values
-    fn unpack_u8_slice(input: &[u8], count: usize) -> Result<(&[u8], &[u8]), ProgramError> {
-        Ok(input.split_at(count))
+    // ensures the per instruction vectors agree with num_instructions, and that
+    // each data size matches the length of its instruction data
+    fn validate_lengths(&self) -> Result<(), AnyIxError> {
+        let num_instructions = self.num_instructions as usize;
+        if self.instruction_data_sizes.len() != num_instructions
+            || self.instruction_account_counts.len() != num_instructions
+            || self.instruction_datas.len() != num_instructions
+        {
+            return Err(AnyIxError::LengthMismatch);
+        }
+        if self
+            .instruction_data_sizes
+            .iter()
+            .zip(self.instruction_datas.iter())
+            .any(|(size, data)| *size as usize != data.len())
+        {
+            return Err(AnyIxError::LengthMismatch);
+        }
+        Ok(())
     }
 }
 
 impl Debug for AnyIx {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("AnyIx")
-        .field("num_instructions", &self.num_instructions)
-        .field("account_couns", &self.instruction_account_counts)
-        .field("instruction_data_sizes", &self.instruction_data_sizes).finish()
+            .field("num_instructions", &self.num_instructions)
+            .field("account_couns", &self.instruction_account_counts)
+            .field("instruction_data_sizes", &self.instruction_data_sizes)
+            .field("extensions", &self.extensions)
+            .finish()
     }
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
+    use solana_program::program_error::ProgramError;
+    use solana_program::program_pack::Pack;
     use solana_program::pubkey::Pubkey;
+    use solana_program::rent::Rent;
     #[test]
     fn test_any_ix() {
         {
@@ -216,20 +381,2103 @@ mod test {
             let want_arb_any = AnyIx {
                 num_instructions: 3,
                 instruction_data_sizes: vec![
-                    ix_1.data.len() as u8,
-                    ix_2.data.len() as u8,
-                    ix_3.data.len() as u8,
+                    ix_1.data.len() as u16,
+                    ix_2.data.len() as u16,
+                    ix_3.data.len() as u16,
                 ],
                 instruction_datas: vec![ix_1.data.clone(), ix_2.data.clone(), ix_3.data.clone()],
-                instruction_account_counts: vec![3, 3, 3],
+                extensions: vec![],
+                instruction_account_counts: vec![4, 4, 4],
             };
             let want_arb_any_data = want_arb_any.pack().unwrap();
             let got_arb_aby = AnyIx::unpack(&want_arb_any_data).unwrap();
             assert_eq!(got_arb_aby, want_arb_any);
 
-            let encoded_ix = encode_instructions(&[ix_1, ix_2, ix_3]);
+            let encoded_ix =
+                encode_instructions(&[ix_1.clone(), ix_2.clone(), ix_3.clone()]).unwrap();
 
             assert_eq!(want_arb_any, encoded_ix);
+
+            let ixs = vec![ix_1, ix_2, ix_3];
+            let (data, accounts) = AnyIxBuilder::new()
+                .add_instructions(ixs.clone())
+                .build()
+                .unwrap();
+            assert_eq!(decode_instructions(&data, &accounts).unwrap(), ixs);
+            assert_eq!(encoded_ix.instructions(&accounts).unwrap(), ixs);
+            assert_eq!(
+                decode_instructions(&data, &accounts[0..accounts.len() - 1]),
+                Err(AnyIxError::AccountUnderflow)
+            );
         }
     }
+    #[test]
+    fn test_any_ix_v1_compat() {
+        let want_arb_any = AnyIx {
+            num_instructions: 2,
+            instruction_data_sizes: vec![3, 1],
+            instruction_account_counts: vec![4, 2],
+            instruction_datas: vec![vec![1, 2, 3], vec![4]],
+            extensions: vec![],
+        };
+        let v1_data = want_arb_any.pack_v1().unwrap();
+        assert_eq!(v1_data, vec![2, 3, 1, 4, 2, 1, 2, 3, 4]);
+        assert_eq!(AnyIx::unpack(&v1_data).unwrap(), want_arb_any);
+        // an empty v1 bundle is still decodable
+        assert_eq!(AnyIx::unpack(&[0]).unwrap().num_instructions, 0);
+    }
+    #[test]
+    fn test_any_ix_v2_large() {
+        let want_arb_any = AnyIx {
+            num_instructions: 2,
+            instruction_data_sizes: vec![300, 2],
+            instruction_account_counts: vec![256, 1],
+            instruction_datas: vec![vec![7; 300], vec![8, 9]],
+            extensions: vec![],
+        };
+        let v2_data = want_arb_any.pack().unwrap();
+        assert_eq!(&v2_data[0..4], &[VERSIONED_TAG, VERSION_2, 2, 0]);
+        assert_eq!(AnyIx::unpack(&v2_data).unwrap(), want_arb_any);
+        assert!(want_arb_any.pack_v1().is_err());
+        let mut unknown_version = v2_data;
+        unknown_version[1] = 4;
+        assert_eq!(
+            AnyIx::unpack(&unknown_version),
+            Err(AnyIxError::UnsupportedVersion)
+        );
+    }
+    #[test]
+    fn test_any_ix_ref() {
+        let arb_any = AnyIx {
+            num_instructions: 3,
+            instruction_data_sizes: vec![3, 0, 2],
+            instruction_account_counts: vec![4, 1, 300],
+            instruction_datas: vec![vec![1, 2, 3], vec![], vec![5, 6]],
+            extensions: vec![],
+        };
+        let packed = arb_any.pack().unwrap();
+        let view = AnyIxRef::unpack(&packed).unwrap();
+        assert_eq!(view.num_instructions(), 3);
+        let entries = view.iter().collect::<Vec<_>>();
+        assert_eq!(
+            entries,
+            vec![(4, &[1u8, 2, 3][..]), (1, &[][..]), (300, &[5u8, 6][..])]
+        );
+        // instruction data is borrowed from the input rather than copied
+        assert!(packed.as_ptr_range().contains(&entries[0].1.as_ptr()));
+        assert_eq!(view.to_owned().unwrap(), arb_any);
+
+        let v1_arb_any = AnyIx {
+            instruction_account_counts: vec![4, 1, 2],
+            ..arb_any
+        };
+        let packed = v1_arb_any.pack_v1().unwrap();
+        let view = AnyIxRef::unpack(&packed).unwrap();
+        assert_eq!(view.iter().len(), 3);
+        assert_eq!(view.to_owned().unwrap(), v1_arb_any);
+    }
+    #[test]
+    fn test_any_ix_builder() {
+        let owner = Pubkey::new_unique();
+        let source = Pubkey::new_unique();
+        let ix_1 = spl_token::instruction::transfer(
+            &spl_token::id(),
+            &source,
+            &Pubkey::new_unique(),
+            &owner,
+            &[],
+            100,
+        )
+        .unwrap();
+        // the owner is only a signer, and the source only writable, in the first
+        // instruction, so both flags must be merged into the second
+        let ix_2 = Instruction {
+            program_id: spl_token::id(),
+            accounts: vec![
+                AccountMeta::new_readonly(source, false),
+                AccountMeta::new_readonly(owner, false),
+            ],
+            data: vec![9; 300],
+        };
+        let target = Pubkey::new_unique();
+        let ix = AnyIxBuilder::new()
+            .add_instruction(ix_1.clone())
+            .add_instruction(ix_2.clone())
+            .instruction(target)
+            .unwrap();
+        assert_eq!(ix.program_id, target);
+        assert_eq!(
+            AnyIx::unpack(&ix.data).unwrap(),
+            encode_instructions(&[ix_1.clone(), ix_2]).unwrap()
+        );
+        let mut want_accounts = vec![AccountMeta::new_readonly(spl_token::id(), false)];
+        want_accounts.extend(ix_1.accounts.iter().cloned());
+        want_accounts.push(AccountMeta::new_readonly(spl_token::id(), false));
+        want_accounts.push(AccountMeta::new(source, false));
+        want_accounts.push(AccountMeta::new_readonly(owner, true));
+        assert_eq!(ix.accounts, want_accounts);
+        let counts = AnyIx::unpack(&ix.data).unwrap().instruction_account_counts;
+        assert_eq!(counts, vec![4, 3]);
+        assert_eq!(counts.iter().sum::<u16>() as usize, ix.accounts.len());
+    }
+    #[test]
+    fn test_any_ix_hash() {
+        let mut builder = AnyIxBuilder::new();
+        for data in [vec![1, 2], vec![3]] {
+            builder.add_instruction(Instruction {
+                program_id: Pubkey::new_unique(),
+                accounts: vec![
+                    AccountMeta::new(Pubkey::new_unique(), false),
+                    AccountMeta::new(Pubkey::new_unique(), false),
+                ],
+                data,
+            });
+        }
+        let anyix = builder.anyix().unwrap();
+        // matches the flags given to every account by `to_account_infos`
+        let metas = builder
+            .account_metas()
+            .into_iter()
+            .map(|meta| AccountMeta::new(meta.pubkey, false))
+            .collect::<Vec<_>>();
+        let mut accounts = accounts_for(&metas);
+        let account_infos = to_account_infos(&mut accounts);
+
+        // the hash does not depend on the version of the payload
+        let v1 = anyix.pack_v1().unwrap();
+        assert_eq!(AnyIxRef::unpack(&v1).unwrap().hash(), anyix.hash());
+        let id = anyix.id(&metas).unwrap();
+        assert_eq!(AnyIxRef::unpack(&v1).unwrap().id(&account_infos), Ok(id));
+
+        let mut swapped = metas.clone();
+        swapped.swap(1, 2);
+        assert_ne!(anyix.id(&swapped).unwrap(), id);
+        let mut readonly = metas.clone();
+        readonly[1].is_writable = false;
+        assert_ne!(anyix.id(&readonly).unwrap(), id);
+        let mut signer = metas.clone();
+        signer[1].is_signer = true;
+        assert_ne!(anyix.id(&signer).unwrap(), id);
+        let mut changed = anyix.clone();
+        changed.instruction_datas[1] = vec![4];
+        assert_ne!(changed.id(&metas).unwrap(), id);
+    }
+
+    #[test]
+    fn test_any_ix_unpack_errors() {
+        let arb_any = AnyIx {
+            num_instructions: 2,
+            instruction_data_sizes: vec![3, 1],
+            instruction_account_counts: vec![4, 2],
+            instruction_datas: vec![vec![1, 2, 3], vec![4]],
+            extensions: vec![],
+        };
+        let packed = arb_any.pack().unwrap();
+        assert_eq!(AnyIx::unpack(&[]), Err(AnyIxError::TruncatedHeader));
+        assert_eq!(
+            AnyIx::unpack(&packed[0..7]),
+            Err(AnyIxError::TruncatedHeader)
+        );
+        assert_eq!(
+            AnyIx::unpack(&packed[0..packed.len() - 1]),
+            Err(AnyIxError::DataUnderflow)
+        );
+        let mut trailing = packed.clone();
+        trailing.push(0);
+        assert_eq!(AnyIx::unpack(&trailing), Err(AnyIxError::TrailingBytes));
+        assert_eq!(AnyIx::unpack(&[2, 3, 1]), Err(AnyIxError::TruncatedHeader));
+        assert_eq!(AnyIx::unpack(&[1, 3, 1, 9]), Err(AnyIxError::DataUnderflow));
+
+        let mut mismatched = arb_any;
+        mismatched.instruction_data_sizes[0] = 2;
+        assert_eq!(mismatched.pack(), Err(AnyIxError::LengthMismatch));
+    }
+    #[test]
+    fn test_any_ix_error_codes() {
+        for err in AnyIxError::ALL {
+            let ProgramError::Custom(code) = ProgramError::from(err) else {
+                panic!("expected custom error");
+            };
+            assert_eq!(AnyIxError::from_code(code), Some(err));
+        }
+        assert_eq!(AnyIxError::from_code(0), None);
+        assert_eq!(
+            AnyIxError::from_code(error::ERROR_CODE_OFFSET + AnyIxError::ALL.len() as u32),
+            None
+        );
+    }
+    #[test]
+    fn test_handle_anyix_errors() {
+        let program_id = Pubkey::new_unique();
+        let mut accounts = test_accounts(3);
+        let data = AnyIx {
+            num_instructions: 1,
+            instruction_data_sizes: vec![0],
+            instruction_account_counts: vec![4],
+            instruction_datas: vec![vec![]],
+            extensions: vec![],
+        }
+        .pack()
+        .unwrap();
+        assert_eq!(
+            handle_anyix(
+                program_id,
+                &to_account_infos(&mut accounts),
+                &data,
+                &AllowAll
+            ),
+            Err(AnyIxError::AccountUnderflow.into())
+        );
+
+        let mut accounts = test_accounts(1);
+        accounts[0].0 = program_id;
+        let data = AnyIx {
+            num_instructions: 1,
+            instruction_data_sizes: vec![0],
+            instruction_account_counts: vec![1],
+            instruction_datas: vec![vec![]],
+            extensions: vec![],
+        }
+        .pack()
+        .unwrap();
+        assert_eq!(
+            handle_anyix(
+                program_id,
+                &to_account_infos(&mut accounts),
+                &data,
+                &AllowAll
+            ),
+            Err(AnyIxError::SelfInvocation.into())
+        );
+        assert_eq!(
+            handle_anyix(program_id, &[], &data[0..3], &AllowAll),
+            Err(AnyIxError::TruncatedHeader.into())
+        );
+    }
+
+    #[test]
+    fn test_handle_anyix_signed() {
+        let program_id = Pubkey::new_unique();
+        let seeds = vec![b"anyix".to_vec(), b"vault".to_vec()];
+        let (vault, bump) = Pubkey::find_program_address(&[b"anyix", b"vault"], &program_id);
+        let destination = Pubkey::new_unique();
+        let ix = spl_token::instruction::transfer(
+            &spl_token::id(),
+            &Pubkey::new_unique(),
+            &destination,
+            &vault,
+            &[],
+            100,
+        )
+        .unwrap();
+        let (data, metas) = AnyIxBuilder::new()
+            .add_signed_instruction(ix.clone(), vault, seeds.clone(), bump)
+            .build()
+            .unwrap();
+        assert!(metas.iter().all(|meta| !meta.is_signer));
+        let mut accounts = accounts_for(&metas);
+        let account_infos = to_account_infos(&mut accounts);
+        record_invocations();
+
+        assert_eq!(
+            handle_anyix(program_id, &account_infos, &data, &AllowAll),
+            Err(AnyIxError::SignerNotAllowed.into())
+        );
+        assert_eq!(
+            handle_anyix_signed(program_id, &account_infos, &data, b"other", &AllowAll),
+            Err(AnyIxError::SignerNamespace.into())
+        );
+        assert_eq!(
+            handle_anyix_signed(program_id, &account_infos, &data, b"", &AllowAll),
+            Err(AnyIxError::SignerNamespace.into())
+        );
+        handle_anyix_signed(program_id, &account_infos, &data, b"anyix", &AllowAll).unwrap();
+        let invoked = take_invocations();
+        assert_eq!(invoked.len(), 1);
+        // the vault is only a signer of the inner instruction
+        let is_signer =
+            |metas: &[AccountMeta]| metas.iter().map(|meta| meta.is_signer).collect::<Vec<_>>();
+        assert_eq!(is_signer(&invoked[0].0.accounts), vec![false, false, true]);
+        assert_eq!(is_signer(&ix.accounts), vec![false, false, true]);
+        let mut want_seeds = seeds.clone();
+        want_seeds.push(vec![bump]);
+        assert_eq!(invoked[0].1, vec![want_seeds]);
+
+        // gated by a config, the bundle is only executed if an authority signs it
+        let authority = Pubkey::new_unique();
+        let (gated_data, gated_metas) = AnyIxBuilder::new()
+            .add_signed_instruction(ix.clone(), vault, seeds.clone(), bump)
+            .add_account(AccountMeta::new_readonly(authority, true))
+            .build()
+            .unwrap();
+        let config = gated_metas.len();
+        let mut gated_accounts = accounts_for(&gated_metas);
+        gated_accounts.extend(test_accounts(1));
+        gated_accounts[config].0 = config_address(&program_id).0;
+        gated_accounts[config].1 = program_id;
+        gated_accounts[config].3 = vec![0; AnyIxConfig::size(1)];
+        AnyIxConfig {
+            authorities: vec![authority],
+        }
+        .pack_into(&mut gated_accounts[config].3)
+        .unwrap();
+        let mut gated_infos = to_account_infos(&mut gated_accounts);
+        let run_gated = |gated_infos: &[AccountInfo]| {
+            handle_anyix_signed_with_config(
+                program_id,
+                &gated_infos[config],
+                &gated_infos[..config],
+                &gated_data,
+                b"anyix",
+                &AllowAll,
+            )
+        };
+        assert_eq!(
+            run_gated(&gated_infos),
+            Err(AnyIxError::Unauthorized.into())
+        );
+        gated_infos[config - 1].is_signer = true;
+        assert_eq!(run_gated(&gated_infos), Ok(()));
+        assert_eq!(take_invocations().len(), 1);
+
+        let mut bad_bump = AnyIx::unpack(&data).unwrap();
+        bad_bump.extensions = vec![Extension::Signer(SignerSeeds {
+            instruction: 0,
+            seeds: seeds.clone(),
+            bump: bump.wrapping_add(1),
+        })];
+        assert_eq!(
+            handle_anyix_signed(
+                program_id,
+                &account_infos,
+                &bad_bump.pack().unwrap(),
+                b"anyix",
+                &AllowAll
+            ),
+            Err(AnyIxError::InvalidSignerSeeds.into())
+        );
+        let mut bad_index = bad_bump;
+        bad_index.extensions = vec![Extension::Signer(SignerSeeds {
+            instruction: 1,
+            seeds,
+            bump,
+        })];
+        assert_eq!(
+            handle_anyix_signed(
+                program_id,
+                &account_infos,
+                &bad_index.pack().unwrap(),
+                b"anyix",
+                &AllowAll
+            ),
+            Err(AnyIxError::InvalidExtension.into())
+        );
+        assert_eq!(
+            AnyIx::unpack(&bad_index.pack().unwrap()).unwrap(),
+            bad_index
+        );
+    }
+
+    #[test]
+    fn test_handle_anyix_policy() {
+        struct NoData;
+        impl AnyIxPolicy for NoData {
+            fn allow_instruction(
+                &self,
+                _idx: usize,
+                _program: &AccountInfo,
+                _accounts: &[AccountInfo],
+                data: &[u8],
+            ) -> bool {
+                data.is_empty()
+            }
+        }
+        struct NoSigners;
+        impl AnyIxPolicy for NoSigners {
+            fn allow_signer_forwarding(&self, _account: &AccountInfo) -> bool {
+                false
+            }
+        }
+
+        let program_id = Pubkey::new_unique();
+        let ix = spl_token::instruction::transfer(
+            &spl_token::id(),
+            &Pubkey::new_unique(),
+            &Pubkey::new_unique(),
+            &Pubkey::new_unique(),
+            &[],
+            100,
+        )
+        .unwrap();
+        let (data, metas) = AnyIxBuilder::new().add_instruction(ix).build().unwrap();
+        let mut accounts = accounts_for(&metas);
+        let mut account_infos = to_account_infos(&mut accounts);
+        record_invocations();
+
+        handle_anyix(
+            program_id,
+            &account_infos,
+            &data,
+            &ProgramAllowlist(&[spl_token::id()]),
+        )
+        .unwrap();
+        handle_anyix(
+            program_id,
+            &account_infos,
+            &data,
+            &ProgramDenylist(&[program_id]),
+        )
+        .unwrap();
+        assert_eq!(take_invocations().len(), 2);
+        assert_eq!(
+            handle_anyix(program_id, &account_infos, &data, &ProgramAllowlist(&[])),
+            Err(AnyIxError::ProgramNotAllowed.into())
+        );
+        assert_eq!(
+            handle_anyix(
+                program_id,
+                &account_infos,
+                &data,
+                &ProgramDenylist(&[spl_token::id()])
+            ),
+            Err(AnyIxError::ProgramNotAllowed.into())
+        );
+        assert_eq!(
+            handle_anyix(program_id, &account_infos, &data, &NoData),
+            Err(AnyIxError::InstructionNotAllowed.into())
+        );
+        handle_anyix(program_id, &account_infos, &data, &NoSigners).unwrap();
+        account_infos[3].is_signer = true;
+        assert_eq!(
+            handle_anyix(program_id, &account_infos, &data, &NoSigners),
+            Err(AnyIxError::SignerForwardingNotAllowed.into())
+        );
+        assert!(take_invocations().len() == 1);
+    }
+
+    #[test]
+    fn test_handle_anyix_slicing() {
+        let program_id = Pubkey::new_unique();
+        let ix_1 = Instruction {
+            program_id: Pubkey::new_unique(),
+            accounts: vec![AccountMeta::new(Pubkey::new_unique(), false)],
+            data: vec![1],
+        };
+        let ix_2 = Instruction {
+            program_id: Pubkey::new_unique(),
+            accounts: vec![
+                AccountMeta::new(Pubkey::new_unique(), false),
+                AccountMeta::new(Pubkey::new_unique(), false),
+            ],
+            data: vec![2, 2],
+        };
+        let ix_3 = Instruction {
+            program_id,
+            accounts: vec![],
+            data: vec![3],
+        };
+        let ixs = vec![ix_1, ix_2, ix_3];
+        let (data, metas) = AnyIxBuilder::new()
+            .add_instructions(ixs.clone())
+            .build()
+            .unwrap();
+        let mut accounts = accounts_for(&metas);
+        let account_infos = to_account_infos(&mut accounts);
+        record_invocations();
+
+        assert_eq!(
+            handle_anyix(program_id, &account_infos, &data, &AllowAll),
+            Err(AnyIxError::SelfInvocation.into())
+        );
+        // the instructions preceding the self invocation were already invoked
+        assert_eq!(take_invocations().len(), 2);
+        let (data, _) = AnyIxBuilder::new()
+            .add_instructions(ixs[0..2].to_vec())
+            .build()
+            .unwrap();
+        handle_anyix(program_id, &account_infos, &data, &AllowAll).unwrap();
+        let invoked = take_invocations();
+        assert_eq!(
+            invoked.into_iter().map(|(ix, _)| ix).collect::<Vec<_>>(),
+            ixs[0..2]
+        );
+
+        #[cfg(feature = "unsafe-exec")]
+        {
+            let (data, _) = AnyIxBuilder::new()
+                .add_instructions(ixs.clone())
+                .build()
+                .unwrap();
+            handle_anyix_unsafe(program_id, &account_infos, &data).unwrap();
+            let invoked = take_invocations();
+            assert_eq!(
+                invoked.into_iter().map(|(ix, _)| ix).collect::<Vec<_>>(),
+                ixs
+            );
+            assert_eq!(
+                handle_anyix_unsafe(program_id, &account_infos[1..], &data),
+                Err(AnyIxError::AccountUnderflow.into())
+            );
+        }
+    }
+
+    #[test]
+    fn test_handle_anyix_return_data_patch() {
+        let program_id = Pubkey::new_unique();
+        let quote = Instruction {
+            program_id: Pubkey::new_unique(),
+            accounts: vec![],
+            data: vec![10, 20, 30, 40],
+        };
+        let swap = Instruction {
+            program_id: Pubkey::new_unique(),
+            accounts: vec![AccountMeta::new(Pubkey::new_unique(), false)],
+            data: vec![0, 0, 0],
+        };
+        let patch = ReturnDataPatch {
+            source: 0,
+            target: 2,
+            source_offset: 1,
+            target_offset: 1,
+            len: 2,
+        };
+        let empty = Instruction {
+            program_id: Pubkey::new_unique(),
+            accounts: vec![],
+            data: vec![],
+        };
+        let mut builder = AnyIxBuilder::new();
+        builder
+            .add_instruction(quote)
+            .add_instruction(empty.clone())
+            .add_instruction(swap.clone());
+        let metas = builder.account_metas();
+        let mut accounts = accounts_for(&metas);
+        let account_infos = to_account_infos(&mut accounts);
+        let run = |patch: ReturnDataPatch| {
+            execute_with(
+                program_id,
+                &account_infos,
+                &builder,
+                [Extension::ReturnDataPatch(patch)],
+            )
+        };
+
+        let invoked = run(patch).unwrap();
+        assert_eq!(invoked[2].0.data, vec![0, 20, 30]);
+        assert_eq!(invoked[2].0.accounts, swap.accounts);
+        assert_eq!(
+            run(ReturnDataPatch { source: 1, ..patch }),
+            Err(AnyIxError::ReturnDataMismatch.into())
+        );
+        assert_eq!(
+            run(ReturnDataPatch { source: 2, ..patch }),
+            Err(AnyIxError::InvalidExtension.into())
+        );
+        assert_eq!(
+            run(ReturnDataPatch {
+                target_offset: 2,
+                ..patch
+            }),
+            Err(AnyIxError::PatchOutOfBounds.into())
+        );
+        assert_eq!(
+            run(ReturnDataPatch {
+                source_offset: 3,
+                ..patch
+            }),
+            Err(AnyIxError::PatchOutOfBounds.into())
+        );
+    }
+
+    #[test]
+    fn test_handle_anyix_account_data_patch() {
+        let program_id = Pubkey::new_unique();
+        let source = Pubkey::new_unique();
+        let owner = Pubkey::new_unique();
+        let ix = spl_token::instruction::transfer(
+            &spl_token::id(),
+            &source,
+            &Pubkey::new_unique(),
+            &owner,
+            &[],
+            0,
+        )
+        .unwrap();
+        let mut builder = AnyIxBuilder::new();
+        builder.add_instruction(ix.clone());
+        let metas = builder.account_metas();
+        let mut accounts = accounts_for(&metas);
+        // the source token account holds 12345 tokens
+        accounts[1].3 = vec![0; spl_token::state::Account::LEN];
+        accounts[1].3[64..72].copy_from_slice(&12345u64.to_le_bytes());
+        let account_infos = to_account_infos(&mut accounts);
+        let run = |patch: AccountDataPatch| {
+            execute_with(
+                program_id,
+                &account_infos,
+                &builder,
+                [Extension::AccountDataPatch(patch)],
+            )
+        };
+
+        let invoked = run(AccountDataPatch::token_amount(0, 1, 1)).unwrap();
+        assert_eq!(
+            invoked[0].0.data,
+            spl_token::instruction::transfer(
+                &spl_token::id(),
+                &source,
+                &ix.accounts[1].pubkey,
+                &owner,
+                &[],
+                12345
+            )
+            .unwrap()
+            .data
+        );
+        assert_eq!(
+            run(AccountDataPatch::token_amount(0, 1, 2)),
+            Err(AnyIxError::PatchOutOfBounds.into())
+        );
+        assert_eq!(
+            run(AccountDataPatch::token_amount(0, 2, 1)),
+            Err(AnyIxError::PatchOutOfBounds.into())
+        );
+        assert_eq!(
+            run(AccountDataPatch::token_amount(0, 4, 1)),
+            Err(AnyIxError::AccountUnderflow.into())
+        );
+        assert_eq!(
+            run(AccountDataPatch::token_amount(1, 1, 1)),
+            Err(AnyIxError::InvalidExtension.into())
+        );
+    }
+
+    #[test]
+    fn test_handle_anyix_assertions() {
+        let program_id = Pubkey::new_unique();
+        let mut builder = AnyIxBuilder::new();
+        builder.add_instruction(
+            spl_token::instruction::transfer(
+                &spl_token::id(),
+                &Pubkey::new_unique(),
+                &Pubkey::new_unique(),
+                &Pubkey::new_unique(),
+                &[],
+                1,
+            )
+            .unwrap(),
+        );
+        let metas = builder.account_metas();
+        let mut accounts = accounts_for(&metas);
+        // the source token account holds 12345 tokens and 100 lamports
+        accounts[1].1 = spl_token::id();
+        accounts[1].2 = 100;
+        accounts[1].3 = vec![0; spl_token::state::Account::LEN];
+        accounts[1].3[64..72].copy_from_slice(&12345u64.to_le_bytes());
+        // the destination is not owned by the token program
+        accounts[2].2 = 100;
+        // the owner is a mint, followed by a token 2022 mint with extensions
+        accounts.extend(test_accounts(1));
+        accounts[3].1 = spl_token::id();
+        accounts[3].3 = vec![0; spl_token::state::Mint::LEN];
+        accounts[4].1 = extension::SPL_TOKEN_2022_PROGRAM_ID;
+        accounts[4].3 = vec![0; spl_token::state::Account::LEN + 5];
+        accounts[4].3[spl_token::state::Account::LEN] = 1;
+        for mint in [3, 4] {
+            accounts[mint].2 = 100;
+            accounts[mint].3[64..72].copy_from_slice(&12345u64.to_le_bytes());
+        }
+        let account_infos = to_account_infos(&mut accounts);
+        let run = |account: u16, balance: Balance, check: BalanceCheck| {
+            let assertion = Assertion {
+                account,
+                balance,
+                check,
+            };
+            execute_with(
+                program_id,
+                &account_infos,
+                &builder,
+                [Extension::Assertion(assertion)],
+            )
+            .map(|invoked| invoked.len())
+        };
+
+        assert_eq!(run(1, Balance::Token, BalanceCheck::Min(12345)), Ok(1));
+        assert_eq!(run(1, Balance::Token, BalanceCheck::MaxDecrease(0)), Ok(1));
+        assert_eq!(
+            run(1, Balance::Lamports, BalanceCheck::ExactDelta(0)),
+            Ok(1)
+        );
+        assert_eq!(
+            run(1, Balance::Token, BalanceCheck::Min(12346)),
+            Err(AnyIxError::AssertionFailed.into())
+        );
+        assert_eq!(
+            run(1, Balance::Lamports, BalanceCheck::ExactDelta(-1)),
+            Err(AnyIxError::AssertionFailed.into())
+        );
+        assert_eq!(
+            run(2, Balance::Token, BalanceCheck::Min(0)),
+            Err(AnyIxError::InvalidTokenAccount.into())
+        );
+        for mint in [3, 4] {
+            assert_eq!(
+                run(mint, Balance::Token, BalanceCheck::Min(0)),
+                Err(AnyIxError::InvalidTokenAccount.into())
+            );
+        }
+        assert_eq!(
+            run(5, Balance::Lamports, BalanceCheck::Min(0)),
+            Err(AnyIxError::AccountUnderflow.into())
+        );
+
+        assert!(BalanceCheck::MaxDecrease(10).check(100, 90));
+        assert!(!BalanceCheck::MaxDecrease(10).check(100, 89));
+        assert!(BalanceCheck::ExactDelta(-10).check(100, 90));
+        assert!(BalanceCheck::ExactDelta(i64::MAX).check(0, i64::MAX as u64));
+    }
+
+    #[test]
+    fn test_handle_anyix_preconditions() {
+        let program_id = Pubkey::new_unique();
+        let mut builder = AnyIxBuilder::new();
+        builder.add_instruction(Instruction {
+            program_id: Pubkey::new_unique(),
+            accounts: vec![AccountMeta::new(Pubkey::new_unique(), false)],
+            data: vec![1],
+        });
+        let metas = builder.account_metas();
+        let mut accounts = accounts_for(&metas);
+        accounts[1].2 = 100;
+        accounts[1].3 = vec![1, 2, 3, 4];
+        let owner = accounts[1].1;
+        let account_infos = to_account_infos(&mut accounts);
+        let run = |check: AccountCheck| {
+            let result = execute_with(
+                program_id,
+                &account_infos,
+                &builder,
+                [Extension::Precondition(Precondition { account: 1, check })],
+            );
+            // nothing is invoked when a precondition fails
+            assert!(take_invocations().is_empty());
+            result.map(|invoked| assert_eq!(invoked.len(), 1))
+        };
+
+        assert_eq!(run(AccountCheck::Owner(owner)), Ok(()));
+        assert_eq!(run(AccountCheck::Lamports(Comparison::Ge, 100)), Ok(()));
+        assert_eq!(
+            run(AccountCheck::Data {
+                offset: 1,
+                bytes: vec![2, 3, 4]
+            }),
+            Ok(())
+        );
+        assert_eq!(
+            run(AccountCheck::DataHash(
+                solana_program::hash::hash(&[1, 2, 3, 4]).to_bytes()
+            )),
+            Ok(())
+        );
+
+        let failed = Err(AnyIxError::PreconditionFailed.into());
+        assert_eq!(run(AccountCheck::Owner(Pubkey::new_unique())), failed);
+        assert_eq!(run(AccountCheck::Lamports(Comparison::Lt, 100)), failed);
+        assert_eq!(
+            run(AccountCheck::Data {
+                offset: 2,
+                bytes: vec![3, 4, 5]
+            }),
+            failed
+        );
+        assert_eq!(run(AccountCheck::DataHash([0; 32])), failed);
+    }
+
+    #[test]
+    fn test_handle_anyix_guards() {
+        let program_id = Pubkey::new_unique();
+        let mut builder = AnyIxBuilder::new();
+        for data in [vec![1], vec![2]] {
+            builder.add_instruction(Instruction {
+                program_id: Pubkey::new_unique(),
+                accounts: vec![AccountMeta::new(Pubkey::new_unique(), false)],
+                data,
+            });
+        }
+        let metas = builder.account_metas();
+        let mut accounts = accounts_for(&metas);
+        // the account of the first instruction is initialized
+        accounts[1].2 = 100;
+        let account_infos = to_account_infos(&mut accounts);
+        let uninitialized = |instruction: u16, account: u16| {
+            Extension::Guard(Guard {
+                instruction,
+                account,
+                check: AccountCheck::Lamports(Comparison::Eq, 0),
+            })
+        };
+        let run = |extensions: Vec<Extension>| {
+            execute_with(program_id, &account_infos, &builder, extensions).map(|invoked| {
+                invoked
+                    .into_iter()
+                    .map(|(ix, _)| ix.data)
+                    .collect::<Vec<_>>()
+            })
+        };
+
+        assert_eq!(
+            run(vec![uninitialized(0, 1), uninitialized(1, 3)]),
+            Ok(vec![vec![2]])
+        );
+        assert_eq!(run(vec![uninitialized(1, 1)]), Ok(vec![vec![1]]));
+        // every guard of an instruction must hold
+        assert_eq!(
+            run(vec![uninitialized(1, 3), uninitialized(1, 1)]),
+            Ok(vec![vec![1]])
+        );
+        assert_eq!(
+            run(vec![uninitialized(2, 1)]),
+            Err(AnyIxError::InvalidExtension.into())
+        );
+        assert_eq!(
+            run(vec![
+                uninitialized(0, 1),
+                Extension::ReturnDataPatch(ReturnDataPatch {
+                    source: 0,
+                    target: 1,
+                    source_offset: 0,
+                    target_offset: 0,
+                    len: 1,
+                }),
+            ]),
+            Err(AnyIxError::ReturnDataMismatch.into())
+        );
+    }
+
+    #[test]
+    fn test_handle_anyix_buffer() {
+        set_syscall_stubs();
+        let program_id = Pubkey::new_unique();
+        let mut builder = AnyIxBuilder::new();
+        for idx in 0..8u8 {
+            builder.add_instruction(Instruction {
+                program_id: Pubkey::new_unique(),
+                accounts: vec![AccountMeta::new(Pubkey::new_unique(), false)],
+                data: vec![idx; 200],
+            });
+        }
+        let (payload, metas) = builder.build().unwrap();
+        let mut accounts = accounts_for(&metas);
+        let account_infos = to_account_infos(&mut accounts);
+
+        let (buffer_key, authority_key, destination_key) = (
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+        );
+        let mut buffer_data = vec![0; buffer::buffer_size(payload.len())];
+        let rent = Rent::default().minimum_balance(buffer_data.len());
+        let (mut buffer_lamports, mut authority_lamports, mut destination_lamports) =
+            (rent - 1, 0, 5);
+        let (mut authority_data, mut destination_data) = (vec![], vec![]);
+        let mut buffer = AccountInfo::new(
+            &buffer_key,
+            false,
+            true,
+            &mut buffer_lamports,
+            &mut buffer_data,
+            &program_id,
+            false,
+            0,
+        );
+        let authority = AccountInfo::new(
+            &authority_key,
+            true,
+            false,
+            &mut authority_lamports,
+            &mut authority_data,
+            &program_id,
+            false,
+            0,
+        );
+        let destination = AccountInfo::new(
+            &destination_key,
+            false,
+            true,
+            &mut destination_lamports,
+            &mut destination_data,
+            &program_id,
+            false,
+            0,
+        );
+        let not_authority = &account_infos[1];
+
+        // an existing account of the program can not be claimed as a buffer
+        assert_eq!(
+            handle_init_buffer(program_id, &buffer, &authority, payload.len() as u32),
+            Err(ProgramError::MissingRequiredSignature)
+        );
+        buffer.is_signer = true;
+        assert_eq!(
+            handle_init_buffer(program_id, &buffer, &authority, payload.len() as u32),
+            Err(ProgramError::AccountNotRentExempt)
+        );
+        **buffer.try_borrow_mut_lamports().unwrap() = rent;
+        assert_eq!(
+            handle_init_buffer(program_id, &buffer, &authority, payload.len() as u32 + 1),
+            Err(AnyIxError::InvalidBuffer.into())
+        );
+        handle_init_buffer(program_id, &buffer, &authority, payload.len() as u32).unwrap();
+        assert_eq!(
+            handle_init_buffer(program_id, &buffer, &authority, payload.len() as u32),
+            Err(AnyIxError::InvalidBuffer.into())
+        );
+        assert_eq!(
+            handle_write_buffer(program_id, &buffer, not_authority, 0, &[1]),
+            Err(ProgramError::MissingRequiredSignature)
+        );
+        for (offset, chunk) in buffer::buffer_chunks(&payload, 900) {
+            handle_write_buffer(program_id, &buffer, &authority, offset, chunk).unwrap();
+        }
+        assert_eq!(
+            handle_write_buffer(program_id, &buffer, &authority, payload.len() as u32, &[1]),
+            Err(AnyIxError::BufferOverflow.into())
+        );
+
+        record_invocations();
+        handle_execute_buffer(
+            program_id,
+            &buffer,
+            &authority,
+            &destination,
+            &account_infos,
+            &AllowAll,
+        )
+        .unwrap();
+        let invoked = take_invocations();
+        assert_eq!(invoked.len(), 8);
+        assert_eq!(invoked[7].0.data, vec![7; 200]);
+        // the buffer is closed, refunding its rent
+        assert_eq!(buffer.lamports(), 0);
+        assert_eq!(destination.lamports(), rent + 5);
+        assert!(buffer.data.borrow().iter().all(|byte| *byte == 0));
+        assert_eq!(
+            handle_execute_buffer(
+                program_id,
+                &buffer,
+                &authority,
+                &destination,
+                &account_infos,
+                &AllowAll,
+            ),
+            Err(AnyIxError::InvalidBuffer.into())
+        );
+    }
+
+    #[test]
+    fn test_handle_anyix_execute_next() {
+        set_syscall_stubs();
+        let program_id = Pubkey::new_unique();
+        let mut builder = AnyIxBuilder::new();
+        for idx in 0..3u8 {
+            builder.add_instruction(Instruction {
+                program_id: Pubkey::new_unique(),
+                accounts: vec![AccountMeta::new(Pubkey::new_unique(), false)],
+                data: vec![idx],
+            });
+        }
+        let (payload, metas) = builder.build().unwrap();
+        let mut accounts = accounts_for(&metas);
+        accounts.extend(test_accounts(4));
+        // two buffers, the second of which expires at slot 10, a destination and an authority
+        for account in accounts[metas.len()..metas.len() + 2].iter_mut() {
+            account.1 = program_id;
+            account.3 = vec![0; buffer::buffer_size(payload.len())];
+            account.2 = Rent::default().minimum_balance(account.3.len());
+        }
+        let mut account_infos = to_account_infos(&mut accounts);
+        for signer in [metas.len(), metas.len() + 1, metas.len() + 3] {
+            account_infos[signer].is_signer = true;
+        }
+        let (bundle_accounts, rest) = account_infos.split_at(metas.len());
+        let (buffer, expiring, destination, authority) = (&rest[0], &rest[1], &rest[2], &rest[3]);
+        let payload_len = payload.len() as u32;
+        handle_init_buffer(program_id, buffer, authority, payload_len).unwrap();
+        handle_init_buffer_with_expiry(program_id, expiring, authority, payload_len, Some(10))
+            .unwrap();
+        for buffer in [buffer, expiring] {
+            handle_write_buffer(program_id, buffer, authority, 0, &payload).unwrap();
+        }
+        let execute_next = |buffer: usize, count: u16| {
+            record_invocations();
+            handle_execute_next(
+                program_id,
+                &rest[buffer],
+                authority,
+                bundle_accounts,
+                &AllowAll,
+                count,
+            )?;
+            Ok::<_, ProgramError>(
+                take_invocations()
+                    .into_iter()
+                    .map(|(ix, _)| ix.data)
+                    .collect::<Vec<_>>(),
+            )
+        };
+
+        assert_eq!(execute_next(0, 2), Ok(vec![vec![0], vec![1]]));
+        assert_eq!(
+            BufferHeader::unpack(&buffer.data.borrow()).unwrap().cursor,
+            2
+        );
+        assert_eq!(buffer::is_complete(&buffer.data.borrow()), Ok(false));
+        assert_eq!(
+            handle_write_buffer(program_id, buffer, authority, 0, &payload),
+            Err(AnyIxError::InvalidBuffer.into())
+        );
+        assert_eq!(
+            handle_execute_buffer(
+                program_id,
+                buffer,
+                authority,
+                destination,
+                bundle_accounts,
+                &AllowAll,
+            ),
+            Err(AnyIxError::InvalidBuffer.into())
+        );
+        // the count is clamped to the remaining instructions
+        assert_eq!(execute_next(0, 2), Ok(vec![vec![2]]));
+        assert_eq!(buffer::is_complete(&buffer.data.borrow()), Ok(true));
+        assert_eq!(execute_next(0, 1), Err(AnyIxError::BundleComplete.into()));
+        handle_close_buffer(program_id, buffer, authority, destination).unwrap();
+        let rent = Rent::default().minimum_balance(buffer.data_len());
+        assert_eq!((buffer.lamports(), destination.lamports()), (0, rent));
+
+        CLOCK_SLOT.with(|slot| slot.set(11));
+        assert_eq!(execute_next(1, 1), Err(AnyIxError::BundleExpired.into()));
+        CLOCK_SLOT.with(|slot| slot.set(10));
+        assert_eq!(execute_next(1, 1), Ok(vec![vec![0]]));
+
+        // preconditions are only checked by the window holding the first instruction,
+        // and assertions can not span windows
+        let mut anyix = AnyIx::unpack(&payload).unwrap();
+        let execute_window = |anyix: &AnyIx, start: u16| {
+            Executor::new(&program_id, &AllowAll)
+                .with_window(start, 1)
+                .execute(
+                    bundle_accounts,
+                    &AnyIxRef::unpack(&anyix.pack().unwrap()).unwrap(),
+                )
+        };
+        anyix.extensions = vec![Extension::Precondition(Precondition {
+            account: 0,
+            check: AccountCheck::Owner(Pubkey::new_unique()),
+        })];
+        assert_eq!(
+            execute_window(&anyix, 0),
+            Err(AnyIxError::PreconditionFailed.into())
+        );
+        assert_eq!(execute_window(&anyix, 1), Ok(()));
+        anyix.extensions = vec![Extension::Assertion(Assertion {
+            account: 0,
+            balance: Balance::Lamports,
+            check: BalanceCheck::Min(0),
+        })];
+        assert_eq!(
+            execute_window(&anyix, 1),
+            Err(AnyIxError::InvalidExtension.into())
+        );
+    }
+
+    #[test]
+    fn test_handle_anyix_execute_next_extensions() {
+        set_syscall_stubs();
+        let program_id = Pubkey::new_unique();
+        let nonce_account = Pubkey::new_unique();
+        let mut builder = AnyIxBuilder::new();
+        for idx in 0..3u8 {
+            builder.add_instruction(Instruction {
+                program_id: Pubkey::new_unique(),
+                accounts: vec![AccountMeta::new(Pubkey::new_unique(), false)],
+                data: vec![idx],
+            });
+        }
+        // extensions index the accounts of the whole bundle, whichever call executes them
+        builder
+            .add_account(AccountMeta::new(nonce_account, false))
+            .add_extension(Extension::Guard(Guard {
+                instruction: 2,
+                account: 5,
+                check: AccountCheck::Lamports(Comparison::Eq, 100),
+            }))
+            .add_extension(Extension::Nonce(Nonce {
+                account: 6,
+                nonce_account,
+                nonce: 0,
+            }));
+        let (payload, metas) = builder.build().unwrap();
+        let mut accounts = accounts_for(&metas);
+        accounts.extend(test_accounts(2));
+        accounts[5].2 = 100;
+        accounts[6].1 = program_id;
+        accounts[6].3 = vec![nonce::NONCE_INITIALIZED, 0, 0, 0, 0, 0, 0, 0, 0];
+        accounts[7].1 = program_id;
+        accounts[7].3 = vec![0; buffer::buffer_size(payload.len())];
+        accounts[7].2 = Rent::default().minimum_balance(accounts[7].3.len());
+        let mut account_infos = to_account_infos(&mut accounts);
+        for signer in [7, 8] {
+            account_infos[signer].is_signer = true;
+        }
+        let (bundle_accounts, rest) = account_infos.split_at(metas.len());
+        let (buffer, authority) = (&rest[0], &rest[1]);
+        handle_init_buffer(program_id, buffer, authority, payload.len() as u32).unwrap();
+        handle_write_buffer(program_id, buffer, authority, 0, &payload).unwrap();
+        let execute_next = |count: u16| {
+            record_invocations();
+            handle_execute_next(
+                program_id,
+                buffer,
+                authority,
+                bundle_accounts,
+                &AllowAll,
+                count,
+            )?;
+            Ok::<_, ProgramError>(
+                take_invocations()
+                    .into_iter()
+                    .map(|(ix, _)| ix.program_id)
+                    .collect::<Vec<_>>(),
+            )
+        };
+
+        // every call slices the accounts of its instructions from the whole bundle
+        assert_eq!(execute_next(2), Ok(vec![metas[0].pubkey, metas[2].pubkey]));
+        assert_eq!(
+            nonce::unpack_nonce(&bundle_accounts[6].data.borrow()),
+            Ok(1)
+        );
+        // the nonce is only consumed by the first call
+        assert_eq!(execute_next(1), Ok(vec![metas[4].pubkey]));
+        assert_eq!(
+            nonce::unpack_nonce(&bundle_accounts[6].data.borrow()),
+            Ok(1)
+        );
+        assert_eq!(buffer::is_complete(&buffer.data.borrow()), Ok(true));
+    }
+
+    #[test]
+    fn test_handle_anyix_with_config() {
+        let program_id = Pubkey::new_unique();
+        let mut builder = AnyIxBuilder::new();
+        builder.add_instruction(Instruction {
+            program_id: Pubkey::new_unique(),
+            accounts: vec![AccountMeta::new(Pubkey::new_unique(), false)],
+            data: vec![1],
+        });
+        let (data, metas) = builder.build().unwrap();
+        // the bundle accounts, followed by the config, two authorities, the system
+        // program, a config at an address other than the config address, and the
+        // config address before it has been created
+        let mut accounts = accounts_for(&metas);
+        accounts.extend(test_accounts(6));
+        accounts[2].0 = config_address(&program_id).0;
+        accounts[7].0 = config_address(&program_id).0;
+        accounts[7].1 = solana_program::system_program::id();
+        for config in [2, 6] {
+            accounts[config].1 = program_id;
+            accounts[config].3 = vec![0; AnyIxConfig::size(2)];
+        }
+        let (first, second) = (accounts[3].0, accounts[4].0);
+        let mut account_infos = to_account_infos(&mut accounts);
+        let execute = |accounts: &[AccountInfo], config: usize| {
+            record_invocations();
+            handle_anyix_with_config(
+                program_id,
+                &accounts[config],
+                &accounts[..2],
+                &data,
+                &AllowAll,
+            )?;
+            Ok::<_, ProgramError>(take_invocations().len())
+        };
+        let init_config = |accounts: &[AccountInfo], config: usize| {
+            handle_init_config(
+                program_id,
+                &accounts[config],
+                &accounts[3],
+                &accounts[5],
+                &first,
+                &[first],
+                2,
+            )
+        };
+
+        assert_eq!(
+            init_config(&account_infos, 2),
+            Err(ProgramError::MissingRequiredSignature)
+        );
+        account_infos[3].is_signer = true;
+        // only the key hard coded by the program may initialize the config
+        assert_eq!(
+            handle_init_config(
+                program_id,
+                &account_infos[2],
+                &account_infos[3],
+                &account_infos[5],
+                &second,
+                &[first],
+                2,
+            ),
+            Err(AnyIxError::Unauthorized.into())
+        );
+        // the system program is not executed by the stubs, so the config is never assigned
+        record_invocations();
+        assert_eq!(
+            init_config(&account_infos, 7),
+            Err(AnyIxError::InvalidConfig.into())
+        );
+        assert_eq!(
+            take_invocations()[0].0,
+            solana_program::system_instruction::create_account(
+                &first,
+                &config_address(&program_id).0,
+                Rent::default().minimum_balance(AnyIxConfig::size(2)),
+                AnyIxConfig::size(2) as u64,
+                &program_id,
+            )
+        );
+        init_config(&account_infos, 2).unwrap();
+        assert_eq!(
+            init_config(&account_infos, 2),
+            Err(AnyIxError::InvalidConfig.into())
+        );
+        assert_eq!(
+            execute(&account_infos, 2),
+            Err(AnyIxError::Unauthorized.into())
+        );
+        // a config at any other address is rejected, even if owned by the program
+        assert_eq!(
+            init_config(&account_infos, 6),
+            Err(AnyIxError::InvalidConfig.into())
+        );
+        AnyIxConfig {
+            authorities: vec![first],
+        }
+        .pack_into(&mut account_infos[6].data.borrow_mut())
+        .unwrap();
+
+        // the authority signs the bundle by being one of its accounts
+        account_infos[1].is_signer = true;
+        account_infos[1].key = &first;
+        assert_eq!(execute(&account_infos, 2), Ok(1));
+        assert_eq!(
+            execute(&account_infos, 6),
+            Err(AnyIxError::InvalidConfig.into())
+        );
+
+        handle_set_authorities(
+            program_id,
+            &account_infos[2],
+            &account_infos[3],
+            &[first, second],
+        )
+        .unwrap();
+        assert_eq!(
+            AnyIxConfig::unpack(&account_infos[2].data.borrow()),
+            Ok(AnyIxConfig {
+                authorities: vec![first, second]
+            })
+        );
+        assert_eq!(
+            handle_set_authorities(
+                program_id,
+                &account_infos[2],
+                &account_infos[3],
+                &[first, second, Pubkey::new_unique()],
+            ),
+            Err(AnyIxError::InvalidConfig.into())
+        );
+        let rotated = Pubkey::new_unique();
+        handle_rotate_authority(program_id, &account_infos[2], &account_infos[3], rotated).unwrap();
+        assert_eq!(
+            AnyIxConfig::unpack(&account_infos[2].data.borrow()),
+            Ok(AnyIxConfig {
+                authorities: vec![rotated, second]
+            })
+        );
+        assert_eq!(
+            handle_rotate_authority(program_id, &account_infos[2], &account_infos[3], first),
+            Err(AnyIxError::Unauthorized.into())
+        );
+        assert_eq!(
+            execute(&account_infos, 2),
+            Err(AnyIxError::Unauthorized.into())
+        );
+    }
+
+    #[test]
+    fn test_handle_anyix_replay_protection() {
+        let program_id = Pubkey::new_unique();
+        let mut builder = AnyIxBuilder::new();
+        builder.add_instruction(Instruction {
+            program_id: Pubkey::new_unique(),
+            accounts: vec![AccountMeta::new(Pubkey::new_unique(), false)],
+            data: vec![1],
+        });
+        let metas = builder.account_metas();
+        // the nonce account is passed after the accounts of the bundle
+        let mut accounts = accounts_for(&metas);
+        accounts.extend(test_accounts(1));
+        accounts[2].1 = program_id;
+        accounts[2].3 = vec![0; nonce::NONCE_ACCOUNT_LEN];
+        let account_infos = to_account_infos(&mut accounts);
+        let run = |extension: Extension| {
+            let result = execute_with(program_id, &account_infos, &builder, [extension]);
+            assert!(take_invocations().is_empty());
+            result.map(|invoked| assert_eq!(invoked.len(), 1))
+        };
+        let nonce_account = *account_infos[2].key;
+        let with_nonce = |nonce: u64| {
+            Extension::Nonce(Nonce {
+                account: 2,
+                nonce_account,
+                nonce,
+            })
+        };
+
+        assert_eq!(
+            run(with_nonce(0)),
+            Err(AnyIxError::InvalidNonceAccount.into())
+        );
+        assert_eq!(
+            handle_init_nonce(program_id, &account_infos[2]),
+            Err(ProgramError::MissingRequiredSignature)
+        );
+        let mut signer = account_infos[2].clone();
+        signer.is_signer = true;
+        handle_init_nonce(program_id, &signer).unwrap();
+        assert_eq!(
+            handle_init_nonce(program_id, &signer),
+            Err(AnyIxError::InvalidNonceAccount.into())
+        );
+        assert_eq!(run(with_nonce(0)), Ok(()));
+        assert_eq!(nonce::unpack_nonce(&account_infos[2].data.borrow()), Ok(1));
+        // replaying the bundle fails
+        assert_eq!(run(with_nonce(0)), Err(AnyIxError::NonceMismatch.into()));
+        assert_eq!(run(with_nonce(1)), Ok(()));
+        assert_eq!(
+            run(Extension::Nonce(Nonce {
+                account: 1,
+                nonce_account: metas[1].pubkey,
+                nonce: 0
+            })),
+            Err(AnyIxError::InvalidNonceAccount.into())
+        );
+        // the nonce account can not be substituted by another holding the same nonce
+        assert_eq!(
+            run(Extension::Nonce(Nonce {
+                account: 2,
+                nonce_account: Pubkey::new_unique(),
+                nonce: 2
+            })),
+            Err(AnyIxError::InvalidNonceAccount.into())
+        );
+
+        CLOCK_SLOT.with(|slot| slot.set(100));
+        assert_eq!(run(Extension::ValidUntil(100)), Ok(()));
+        assert_eq!(
+            run(Extension::ValidUntil(99)),
+            Err(AnyIxError::BundleExpired.into())
+        );
+    }
+
+    #[test]
+    fn test_handle_anyix_ed25519() {
+        use solana_program::sysvar::instructions;
+
+        let program_id = Pubkey::new_unique();
+        let authority = Pubkey::new_unique();
+        let nonce_account = Pubkey::new_unique();
+        let mut builder = AnyIxBuilder::new();
+        builder
+            .add_instruction(Instruction {
+                program_id: Pubkey::new_unique(),
+                accounts: vec![AccountMeta::new(Pubkey::new_unique(), false)],
+                data: vec![1],
+            })
+            .add_account(AccountMeta::new(nonce_account, false));
+        let metas = builder.account_metas();
+        // the bundle accounts, ending with the nonce account, followed by the config
+        // and instructions sysvar
+        let mut accounts = accounts_for(&metas);
+        accounts.extend(test_accounts(2));
+        accounts[2].1 = program_id;
+        accounts[2].3 = vec![nonce::NONCE_INITIALIZED, 0, 0, 0, 0, 0, 0, 0, 0];
+        accounts[3].0 = config_address(&program_id).0;
+        accounts[3].1 = program_id;
+        accounts[3].3 = vec![0; AnyIxConfig::size(1)];
+        AnyIxConfig {
+            authorities: vec![authority],
+        }
+        .pack_into(&mut accounts[3].3)
+        .unwrap();
+        accounts[4].0 = instructions::id();
+        let mut account_infos = to_account_infos(&mut accounts);
+        for (account, meta) in account_infos.iter_mut().zip(metas.iter()) {
+            account.is_writable = meta.is_writable;
+        }
+        let bundle = |nonce: Option<u64>, commit_accounts: bool| {
+            let mut builder = builder.clone();
+            if let Some(nonce) = nonce {
+                builder.add_extension(Extension::Nonce(Nonce {
+                    account: 2,
+                    nonce_account,
+                    nonce,
+                }));
+            }
+            if commit_accounts {
+                builder.commit_accounts();
+            }
+            builder.anyix().unwrap().pack().unwrap()
+        };
+        // builds the instructions sysvar for a transaction holding `preceding`
+        // followed by the instruction executing the bundle
+        let sysvar_data = |preceding: &[Instruction]| {
+            let borrowed = preceding
+                .iter()
+                .map(|ix| instructions::BorrowedInstruction {
+                    program_id: &ix.program_id,
+                    accounts: vec![],
+                    data: &ix.data,
+                })
+                .chain(std::iter::once(instructions::BorrowedInstruction {
+                    program_id: &program_id,
+                    accounts: vec![],
+                    data: &[],
+                }))
+                .collect::<Vec<_>>();
+            let mut data = instructions::construct_instructions_data(&borrowed);
+            instructions::store_current_index(&mut data, preceding.len() as u16);
+            data
+        };
+        let run = |account_infos: &[AccountInfo], data: &[u8]| {
+            record_invocations();
+            handle_anyix_ed25519(
+                program_id,
+                &account_infos[3],
+                &account_infos[4],
+                &account_infos[..3],
+                data,
+                &AllowAll,
+            )?;
+            Ok::<_, ProgramError>(take_invocations().len())
+        };
+        let signature = [7u8; 64];
+
+        let data = bundle(Some(0), true);
+        let message = bundle_message(&data, 0, &program_id);
+        let mut sysvar = sysvar_data(&[ed25519_instruction(&authority, &signature, &message)]);
+        account_infos[4].data = std::rc::Rc::new(std::cell::RefCell::new(&mut sysvar[..]));
+        assert_eq!(run(&account_infos, &data), Ok(1));
+        assert_eq!(nonce::unpack_nonce(&account_infos[2].data.borrow()), Ok(1));
+        // the nonce has been consumed, so the bundle can not be replayed
+        assert_eq!(
+            run(&account_infos, &data),
+            Err(AnyIxError::NonceMismatch.into())
+        );
+
+        let data = bundle(Some(1), true);
+        assert_eq!(
+            run(&account_infos, &data),
+            Err(AnyIxError::SignatureNotFound.into())
+        );
+        let message = bundle_message(&data, 1, &program_id);
+        let mut sysvar = sysvar_data(&[ed25519_instruction(
+            &Pubkey::new_unique(),
+            &signature,
+            &message,
+        )]);
+        account_infos[4].data = std::rc::Rc::new(std::cell::RefCell::new(&mut sysvar[..]));
+        assert_eq!(
+            run(&account_infos, &data),
+            Err(AnyIxError::SignatureNotFound.into())
+        );
+        assert_eq!(
+            run(&account_infos, &bundle(None, true)),
+            Err(AnyIxError::MissingNonce.into())
+        );
+        let data = bundle(Some(1), false);
+        let message = bundle_message(&data, 1, &program_id);
+        let mut sysvar = sysvar_data(&[ed25519_instruction(&authority, &signature, &message)]);
+        account_infos[4].data = std::rc::Rc::new(std::cell::RefCell::new(&mut sysvar[..]));
+        assert_eq!(
+            run(&account_infos, &data),
+            Err(AnyIxError::MissingAccountCommitment.into())
+        );
+        // the signed bundle can not be executed with other accounts
+        let data = bundle(Some(1), true);
+        let message = bundle_message(&data, 1, &program_id);
+        let mut sysvar = sysvar_data(&[ed25519_instruction(&authority, &signature, &message)]);
+        account_infos[4].data = std::rc::Rc::new(std::cell::RefCell::new(&mut sysvar[..]));
+        let substituted = Pubkey::new_unique();
+        account_infos[1].key = &substituted;
+        assert_eq!(
+            run(&account_infos, &data),
+            Err(AnyIxError::AccountCommitmentMismatch.into())
+        );
+        account_infos[1].key = &metas[1].pubkey;
+        assert_eq!(run(&account_infos, &data), Ok(1));
+    }
+
+    #[test]
+    fn test_handle_anyix_account_commitment() {
+        let program_id = Pubkey::new_unique();
+        let mut builder = AnyIxBuilder::new();
+        builder
+            .add_instruction(Instruction {
+                program_id: Pubkey::new_unique(),
+                accounts: vec![
+                    AccountMeta::new(Pubkey::new_unique(), false),
+                    AccountMeta::new_readonly(Pubkey::new_unique(), false),
+                ],
+                data: vec![1],
+            })
+            .add_account(AccountMeta::new(Pubkey::new_unique(), false))
+            .commit_accounts();
+        let (data, metas) = builder.build().unwrap();
+        assert_eq!(metas.len(), 4);
+        assert!(matches!(
+            AnyIx::unpack(&data).unwrap().extensions[..],
+            [Extension::AccountCommitment(_)]
+        ));
+        let mut accounts = accounts_for(&metas);
+        accounts.extend(test_accounts(1));
+        let mut account_infos = to_account_infos(&mut accounts);
+        for (account, meta) in account_infos.iter_mut().zip(metas.iter()) {
+            account.is_writable = meta.is_writable;
+        }
+        let run = |account_infos: &[AccountInfo]| {
+            record_invocations();
+            handle_anyix(program_id, account_infos, &data, &AllowAll)?;
+            Ok::<_, ProgramError>(take_invocations().len())
+        };
+
+        assert_eq!(run(&account_infos[..4]), Ok(1));
+        let mismatch = Err(AnyIxError::AccountCommitmentMismatch.into());
+        // trailing accounts are covered by the commitment
+        assert_eq!(run(&account_infos), mismatch);
+        account_infos.swap(1, 2);
+        assert_eq!(run(&account_infos[..4]), mismatch);
+        account_infos.swap(1, 2);
+        account_infos[2].is_writable = true;
+        assert_eq!(run(&account_infos[..4]), mismatch);
+        account_infos[2].is_writable = false;
+        account_infos[3].key = account_infos[4].key;
+        assert_eq!(run(&account_infos[..4]), mismatch);
+    }
+
+    #[test]
+    fn test_handle_anyix_account_table() {
+        let program_id = Pubkey::new_unique();
+        let (source, owner) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let ixs = (1..=3)
+            .map(|amount| {
+                spl_token::instruction::transfer(
+                    &spl_token::id(),
+                    &source,
+                    &Pubkey::new_unique(),
+                    &owner,
+                    &[],
+                    amount,
+                )
+                .unwrap()
+            })
+            .collect::<Vec<_>>();
+        let mut builder = AnyIxBuilder::new();
+        builder.add_instructions(ixs.clone());
+        let (sliced_data, sliced_metas) = builder.build().unwrap();
+        let (data, metas) = builder.use_account_table().build().unwrap();
+        // the token program, source and owner are only listed once
+        assert_eq!(sliced_metas.len(), 12);
+        assert_eq!(metas.len(), 6);
+        assert!(data.len() + metas.len() * 32 < sliced_data.len() + sliced_metas.len() * 32);
+        assert_eq!(decode_instructions(&data, &metas).unwrap(), ixs);
+
+        let mut accounts = accounts_for(&metas);
+        let account_infos = to_account_infos(&mut accounts);
+        record_invocations();
+        handle_anyix(program_id, &account_infos, &data, &AllowAll).unwrap();
+        let invoked = take_invocations()
+            .into_iter()
+            .map(|(ix, _)| ix)
+            .collect::<Vec<_>>();
+        assert_eq!(invoked.len(), 3);
+        for (invoked, ix) in invoked.iter().zip(ixs.iter()) {
+            assert_eq!(invoked.program_id, ix.program_id);
+            assert_eq!(invoked.data, ix.data);
+            assert_eq!(
+                invoked
+                    .accounts
+                    .iter()
+                    .map(|meta| meta.pubkey)
+                    .collect::<Vec<_>>(),
+                ix.accounts
+                    .iter()
+                    .map(|meta| meta.pubkey)
+                    .collect::<Vec<_>>()
+            );
+        }
+
+        let with_indices = |indices: Vec<u8>| {
+            let mut anyix = AnyIx::unpack(&data).unwrap();
+            anyix.extensions = vec![Extension::AccountIndices(indices)];
+            anyix.pack().unwrap()
+        };
+        assert_eq!(
+            handle_anyix(
+                program_id,
+                &account_infos,
+                &with_indices(vec![0; 11]),
+                &AllowAll
+            ),
+            Err(AnyIxError::InvalidExtension.into())
+        );
+        assert_eq!(
+            handle_anyix(
+                program_id,
+                &account_infos,
+                &with_indices(vec![6; 12]),
+                &AllowAll
+            ),
+            Err(AnyIxError::AccountUnderflow.into())
+        );
+    }
+
+    #[test]
+    fn test_handle_anyix_remaining_accounts() {
+        let program_id = Pubkey::new_unique();
+        let ixs = (1..=2)
+            .map(|count| Instruction {
+                program_id: Pubkey::new_unique(),
+                accounts: (0..count)
+                    .map(|_| AccountMeta::new(Pubkey::new_unique(), false))
+                    .collect(),
+                data: vec![count],
+            })
+            .collect::<Vec<_>>();
+        let (data, metas) = AnyIxBuilder::new()
+            .add_instructions(ixs.clone())
+            .build()
+            .unwrap();
+        // the program's own config account precedes the bundle's accounts
+        let mut accounts = test_accounts(1);
+        accounts.extend(accounts_for(&metas));
+        accounts.extend(test_accounts(1));
+        let account_infos = to_account_infos(&mut accounts);
+        record_invocations();
+
+        handle_anyix_at(program_id, &account_infos[..6], 1, &data, &AllowAll).unwrap();
+        assert_eq!(
+            take_invocations()
+                .into_iter()
+                .map(|(ix, _)| ix)
+                .collect::<Vec<_>>(),
+            ixs
+        );
+        handle_anyix_remaining(program_id, &account_infos[1..6], &data, &AllowAll).unwrap();
+        assert_eq!(take_invocations().len(), 2);
+        // nothing is invoked when the accounts do not match the bundle
+        assert_eq!(
+            handle_anyix_at(program_id, &account_infos, 1, &data, &AllowAll),
+            Err(AnyIxError::TrailingAccounts.into())
+        );
+        assert_eq!(
+            handle_anyix_remaining(program_id, &account_infos[1..5], &data, &AllowAll),
+            Err(AnyIxError::AccountUnderflow.into())
+        );
+        assert_eq!(
+            handle_anyix_at(program_id, &account_infos, 8, &data, &AllowAll),
+            Err(AnyIxError::AccountUnderflow.into())
+        );
+        assert!(take_invocations().is_empty());
+        // accounts referenced by extensions are used by the bundle
+        let mut anyix = AnyIx::unpack(&data).unwrap();
+        anyix.extensions = vec![Extension::Precondition(Precondition {
+            account: 5,
+            check: AccountCheck::Owner(accounts[6].1),
+        })];
+        let account_infos = to_account_infos(&mut accounts);
+        handle_anyix_at(
+            program_id,
+            &account_infos,
+            1,
+            &anyix.pack().unwrap(),
+            &AllowAll,
+        )
+        .unwrap();
+        assert_eq!(take_invocations().len(), 2);
+    }
+
+    #[cfg(feature = "anchor")]
+    #[test]
+    fn test_anchor() {
+        use crate::anchor::{
+            anyix_with_config, into_anchor_error, AnyIxErrorCode, AnyIxWithConfig,
+            AnyIxWithConfigBumps,
+        };
+        use anchor_lang::prelude::{Context, Signer, UncheckedAccount};
+        use anchor_lang::Accounts;
+
+        for err in AnyIxError::ALL {
+            let code = AnyIxErrorCode::from(err);
+            assert_eq!(u32::from(code), err.code());
+            assert_eq!(code.to_string(), err.to_string());
+            assert_eq!(into_anchor_error(err.into()), code.into());
+        }
+
+        let program_id = Pubkey::new_unique();
+        let mut builder = AnyIxBuilder::new();
+        builder.add_instruction(Instruction {
+            program_id: Pubkey::new_unique(),
+            accounts: vec![AccountMeta::new(Pubkey::new_unique(), false)],
+            data: vec![1],
+        });
+        let (data, metas) = builder.build().unwrap();
+        // the authority and config, followed by the bundle accounts, a signer which
+        // is not an authority, and a config at an address other than the config address
+        let mut accounts = test_accounts(2);
+        accounts.extend(accounts_for(&metas));
+        accounts.extend(test_accounts(2));
+        accounts[1].0 = config_address(&program_id).0;
+        for config in [1, 5] {
+            accounts[config].1 = program_id;
+            accounts[config].3 = vec![0; AnyIxConfig::size(1)];
+        }
+        let mut account_infos = to_account_infos(&mut accounts);
+        account_infos[0].is_signer = true;
+        account_infos[4].is_signer = true;
+        let authority = *account_infos[0].key;
+        // the config is already owned by the program, so the system program is not invoked
+        handle_init_config(
+            program_id,
+            &account_infos[1],
+            &account_infos[0],
+            &account_infos[4],
+            &authority,
+            &[authority],
+            1,
+        )
+        .unwrap();
+        AnyIxConfig {
+            authorities: vec![authority],
+        }
+        .pack_into(&mut account_infos[5].data.borrow_mut())
+        .unwrap();
+        // executes with `accounts` as the accounts of the instruction, as anchor would
+        fn execute<'info>(
+            program_id: &Pubkey,
+            accounts: &'info [AccountInfo<'info>],
+            data: &[u8],
+        ) -> anchor_lang::Result<usize> {
+            let mut remaining_accounts = accounts;
+            let mut bumps = AnyIxWithConfigBumps::default();
+            let mut ctx_accounts = AnyIxWithConfig::try_accounts(
+                program_id,
+                &mut remaining_accounts,
+                data,
+                &mut bumps,
+                &mut Default::default(),
+            )?;
+            let ctx = Context::new(program_id, &mut ctx_accounts, remaining_accounts, bumps);
+            record_invocations();
+            anyix_with_config(&ctx, data, &AllowAll)?;
+            Ok(take_invocations().len())
+        }
+
+        assert_eq!(execute(&program_id, &account_infos[..4], &data), Ok(1));
+        assert_eq!(
+            execute(&program_id, &account_infos, &data),
+            Err(AnyIxErrorCode::TrailingAccounts.into())
+        );
+        let unauthorized = [4, 1, 2, 3].map(|idx| account_infos[idx].clone());
+        assert_eq!(
+            execute(&program_id, &unauthorized, &data),
+            Err(AnyIxErrorCode::Unauthorized.into())
+        );
+        let other_config = [0, 5, 2, 3].map(|idx| account_infos[idx].clone());
+        assert_eq!(
+            execute(&program_id, &other_config, &data),
+            Err(anchor_lang::error::ErrorCode::ConstraintSeeds.into())
+        );
+        // the config is checked even if the accounts do not come from `try_accounts`
+        let mut ctx_accounts = AnyIxWithConfig {
+            authority: Signer::try_from(&account_infos[0]).unwrap(),
+            config: UncheckedAccount::try_from(&account_infos[5]),
+        };
+        let ctx = Context::new(
+            &program_id,
+            &mut ctx_accounts,
+            &account_infos[2..4],
+            AnyIxWithConfigBumps::default(),
+        );
+        assert_eq!(
+            anyix_with_config(&ctx, &data, &AllowAll),
+            Err(AnyIxErrorCode::InvalidConfig.into())
+        );
+    }
+
+    #[test]
+    fn test_anyix_fallback() {
+        fn process(_: &Pubkey, _: &[AccountInfo], data: &[u8]) -> ProgramResult {
+            Err(ProgramError::Custom(data.len() as u32))
+        }
+        crate::anyix_fallback!(process_instruction, process, AllowAll);
+        crate::anyix_fallback!(process_short, process, AllowAll, discriminator = [0xff]);
+
+        assert_eq!(sighash("global", "anyix"), ANYIX_DISCRIMINATOR);
+        let program_id = Pubkey::new_unique();
+        let ix = Instruction {
+            program_id: Pubkey::new_unique(),
+            accounts: vec![AccountMeta::new(Pubkey::new_unique(), false)],
+            data: vec![1],
+        };
+        let (packed, metas) = AnyIxBuilder::new()
+            .add_instruction(ix.clone())
+            .build()
+            .unwrap();
+        let mut accounts = accounts_for(&metas);
+        let account_infos = to_account_infos(&mut accounts);
+        record_invocations();
+
+        let data = fallback_data(&ANYIX_DISCRIMINATOR, &packed);
+        process_instruction(&program_id, &account_infos, &data).unwrap();
+        assert_eq!(take_invocations().len(), 1);
+        // anything else, including a bundle without its discriminator, reaches the
+        // program's own processor
+        assert_eq!(
+            process_instruction(&program_id, &account_infos, &packed),
+            Err(ProgramError::Custom(packed.len() as u32))
+        );
+        assert_eq!(
+            process_instruction(&program_id, &account_infos, &data[..7]),
+            Err(ProgramError::Custom(7))
+        );
+        assert_eq!(
+            process_short(&program_id, &account_infos, &data),
+            Err(ProgramError::Custom(data.len() as u32))
+        );
+        process_short(
+            &program_id,
+            &account_infos,
+            &fallback_data(&[0xff], &packed),
+        )
+        .unwrap();
+        assert_eq!(
+            process_fallback(
+                &program_id,
+                &account_infos,
+                &packed,
+                &[],
+                &AllowAll,
+                process
+            ),
+            Err(ProgramError::Custom(packed.len() as u32))
+        );
+        assert_eq!(take_invocations().len(), 1);
+    }
+
+    #[cfg(all(feature = "events", feature = "client"))]
+    #[test]
+    fn test_anyix_events() {
+        let program_id = Pubkey::new_unique();
+        let ixs = (1..=2)
+            .map(|count| Instruction {
+                program_id: Pubkey::new_unique(),
+                accounts: (0..count)
+                    .map(|_| AccountMeta::new(Pubkey::new_unique(), false))
+                    .collect(),
+                data: vec![count; count as usize],
+            })
+            .collect::<Vec<_>>();
+        let (data, metas) = AnyIxBuilder::new()
+            .add_instructions(ixs.clone())
+            .build()
+            .unwrap();
+        let mut accounts = accounts_for(&metas);
+        let account_infos = to_account_infos(&mut accounts);
+        record_invocations();
+        take_log_data();
+        handle_anyix(program_id, &account_infos, &data, &AllowAll).unwrap();
+        let mut logs = vec![format!("Program {program_id} invoke [1]")];
+        logs.extend(take_log_data());
+        logs.push(format!("Program {program_id} success"));
+        let mut expected = ixs
+            .iter()
+            .enumerate()
+            .map(|(idx, ix)| {
+                AnyIxEvent::Instruction(InstructionEvent {
+                    index: idx as u16,
+                    program_id: ix.program_id,
+                    account_count: ix.accounts.len() as u16,
+                    data_hash: solana_program::hash::hash(&ix.data).to_bytes(),
+                    compute_units: 1000,
+                })
+            })
+            .collect::<Vec<_>>();
+        expected.push(AnyIxEvent::Summary(SummaryEvent {
+            invoked: 2,
+            skipped: 0,
+            compute_units: 2000,
+        }));
+        assert_eq!(decode_events(&program_id, &logs), expected);
+    }
+
+    type TestAccount = (Pubkey, Pubkey, u64, Vec<u8>);
+
+    type Invocation = (Instruction, Vec<Vec<Vec<u8>>>);
+
+    thread_local! {
+        static INVOCATIONS: std::cell::RefCell<Vec<Invocation>> = Default::default();
+        static RETURN_DATA: std::cell::RefCell<Option<(Pubkey, Vec<u8>)>> = Default::default();
+        static CLOCK_SLOT: std::cell::Cell<u64> = Default::default();
+        static LOG_DATA: std::cell::RefCell<Vec<String>> = Default::default();
+        static COMPUTE_UNITS: std::cell::Cell<u64> = const { std::cell::Cell::new(200_000) };
+    }
+
+    // records cross program invocations made by the calling thread, instead of
+    // the default stub which discards them. invoked programs echo their
+    // instruction data as return data, unless it is empty, and the clock reports
+    // the slot set in `CLOCK_SLOT`, with the default rent. every invocation
+    // consumes 1000 compute units, and records logged with `sol_log_data` are
+    // kept in `LOG_DATA`
+    struct TestSyscallStubs;
+
+    impl solana_program::program_stubs::SyscallStubs for TestSyscallStubs {
+        fn sol_invoke_signed(
+            &self,
+            instruction: &Instruction,
+            _account_infos: &[AccountInfo],
+            signers_seeds: &[&[&[u8]]],
+        ) -> ProgramResult {
+            let signers_seeds = signers_seeds
+                .iter()
+                .map(|seeds| seeds.iter().map(|seed| seed.to_vec()).collect())
+                .collect();
+            INVOCATIONS.with(|invocations| {
+                invocations
+                    .borrow_mut()
+                    .push((instruction.clone(), signers_seeds))
+            });
+            RETURN_DATA.with(|return_data| {
+                *return_data.borrow_mut() = Some((instruction.program_id, instruction.data.clone()))
+                    .filter(|(_, data)| !data.is_empty())
+            });
+            COMPUTE_UNITS.with(|units| units.set(units.get() - 1000));
+            Ok(())
+        }
+        fn sol_remaining_compute_units(&self) -> u64 {
+            COMPUTE_UNITS.with(|units| units.get())
+        }
+        fn sol_log_data(&self, fields: &[&[u8]]) {
+            use base64::Engine;
+            let fields = fields
+                .iter()
+                .map(|field| base64::engine::general_purpose::STANDARD.encode(field))
+                .collect::<Vec<_>>();
+            LOG_DATA.with(|logs| {
+                logs.borrow_mut()
+                    .push(format!("Program data: {}", fields.join(" ")))
+            });
+        }
+        fn sol_get_return_data(&self) -> Option<(Pubkey, Vec<u8>)> {
+            RETURN_DATA.with(|return_data| return_data.borrow().clone())
+        }
+        fn sol_get_rent_sysvar(&self, var_addr: *mut u8) -> u64 {
+            let rent = Rent::default();
+            unsafe { *(var_addr as *mut Rent) = rent };
+            solana_program::entrypoint::SUCCESS
+        }
+        fn sol_get_clock_sysvar(&self, var_addr: *mut u8) -> u64 {
+            let clock = solana_program::clock::Clock {
+                slot: CLOCK_SLOT.with(|slot| slot.get()),
+                ..Default::default()
+            };
+            unsafe { *(var_addr as *mut solana_program::clock::Clock) = clock };
+            solana_program::entrypoint::SUCCESS
+        }
+    }
+
+    // installs the syscall stubs, which sysvars are read from
+    fn set_syscall_stubs() {
+        static INIT: std::sync::Once = std::sync::Once::new();
+        INIT.call_once(|| {
+            solana_program::program_stubs::set_syscall_stubs(Box::new(TestSyscallStubs));
+        });
+    }
+
+    // starts recording the invocations made by the calling thread
+    fn record_invocations() {
+        set_syscall_stubs();
+        INVOCATIONS.with(|invocations| invocations.take());
+    }
+
+    // returns the records logged by the calling thread since the last call
+    #[cfg(all(feature = "events", feature = "client"))]
+    fn take_log_data() -> Vec<String> {
+        LOG_DATA.with(|logs| logs.take())
+    }
+
+    // returns the invocations made by the calling thread since the last call
+    fn take_invocations() -> Vec<Invocation> {
+        INVOCATIONS.with(|invocations| invocations.take())
+    }
+
+    // executes the bundle of `builder`, extended with `extensions`, returning the
+    // instructions it invoked. the invocations of a failed execution are left
+    // for the caller to take
+    fn execute_with(
+        program_id: Pubkey,
+        accounts: &[AccountInfo],
+        builder: &AnyIxBuilder,
+        extensions: impl IntoIterator<Item = Extension>,
+    ) -> Result<Vec<Invocation>, ProgramError> {
+        let mut builder = builder.clone();
+        for extension in extensions {
+            builder.add_extension(extension);
+        }
+        let anyix = builder.anyix()?;
+        let data = anyix.pack()?;
+        assert_eq!(AnyIx::unpack(&data)?, anyix);
+        record_invocations();
+        handle_anyix(program_id, accounts, &data, &AllowAll)?;
+        Ok(take_invocations())
+    }
+
+    // returns a test account for each of `metas`, with the key of the meta
+    fn accounts_for(metas: &[AccountMeta]) -> Vec<TestAccount> {
+        let mut accounts = test_accounts(metas.len());
+        for (account, meta) in accounts.iter_mut().zip(metas) {
+            account.0 = meta.pubkey;
+        }
+        accounts
+    }
+
+    fn test_accounts(count: usize) -> Vec<TestAccount> {
+        (0..count)
+            .map(|_| (Pubkey::new_unique(), Pubkey::new_unique(), 0, vec![]))
+            .collect()
+    }
+
+    fn to_account_infos(accounts: &mut [TestAccount]) -> Vec<AccountInfo<'_>> {
+        accounts
+            .iter_mut()
+            .map(|(key, owner, lamports, data)| {
+                AccountInfo::new(key, false, true, lamports, data, owner, false, 0)
+            })
+            .collect()
+    }
 }
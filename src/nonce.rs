@@ -0,0 +1,69 @@
+//! nonce accounts protect bundles against replay. a bundle carrying a nonce
+//! extension is only executed if the nonce matches the value held by the nonce
+//! account, which is then incremented. a nonce account is created by the client
+//! from a fresh keypair with `NONCE_ACCOUNT_LEN` bytes and assigned to the
+//! executing program, which then initializes it with `handle_init_nonce`, signed
+//! by the nonce account.
+//!
+//! a nonce account is laid out as `[initialized u8][nonce u64]`
+
+use solana_program::account_info::AccountInfo;
+use solana_program::entrypoint::ProgramResult;
+use solana_program::program_error::ProgramError;
+use solana_program::pubkey::Pubkey;
+
+use crate::AnyIxError;
+
+/// the first byte of an initialized nonce account
+pub const NONCE_INITIALIZED: u8 = 3;
+
+/// the size of a nonce account
+pub const NONCE_ACCOUNT_LEN: usize = 1 + 8;
+
+/// returns the current nonce, given the data of a nonce account
+pub fn unpack_nonce(data: &[u8]) -> Result<u64, AnyIxError> {
+    if data.len() != NONCE_ACCOUNT_LEN || data[0] != NONCE_INITIALIZED {
+        return Err(AnyIxError::InvalidNonceAccount);
+    }
+    let mut nonce = [0u8; 8];
+    nonce.copy_from_slice(&data[1..]);
+    Ok(u64::from_le_bytes(nonce))
+}
+
+/// initializes `nonce_account`, which must sign and be owned by `program_id`, with
+/// a nonce of 0
+pub fn handle_init_nonce(program_id: Pubkey, nonce_account: &AccountInfo<'_>) -> ProgramResult {
+    if !nonce_account.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    if !nonce_account.owner.eq(&program_id) {
+        return Err(AnyIxError::InvalidNonceAccount.into());
+    }
+    let mut data = nonce_account.try_borrow_mut_data()?;
+    if data.len() != NONCE_ACCOUNT_LEN || data[0] != 0 {
+        return Err(AnyIxError::InvalidNonceAccount.into());
+    }
+    data[0] = NONCE_INITIALIZED;
+    Ok(())
+}
+
+// checks the nonce held by `nonce_account` equals `nonce`, then increments it
+pub(crate) fn advance_nonce(
+    program_id: &Pubkey,
+    nonce_account: &AccountInfo<'_>,
+    nonce: u64,
+) -> ProgramResult {
+    if !nonce_account.owner.eq(program_id) || !nonce_account.is_writable {
+        return Err(AnyIxError::InvalidNonceAccount.into());
+    }
+    let mut data = nonce_account.try_borrow_mut_data()?;
+    let current = unpack_nonce(&data)?;
+    if current != nonce {
+        return Err(AnyIxError::NonceMismatch.into());
+    }
+    let next = current
+        .checked_add(1)
+        .ok_or(AnyIxError::InvalidNonceAccount)?;
+    data[1..].copy_from_slice(&next.to_le_bytes());
+    Ok(())
+}
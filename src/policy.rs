@@ -0,0 +1,57 @@
+use solana_program::account_info::AccountInfo;
+use solana_program::pubkey::Pubkey;
+
+/// hooks consulted by `handle_anyix` before each inner instruction is invoked,
+/// allowing a program to restrict what a bundle may do. every hook defaults to
+/// allowing the action, so implementations only need to override the hooks they
+/// care about.
+///
+/// self invocation is always rejected, regardless of the policy
+pub trait AnyIxPolicy {
+    /// returns false to reject invoking `program_id`
+    fn allow_program(&self, _program_id: &Pubkey) -> bool {
+        true
+    }
+    /// returns false to reject the instruction at index `idx`, which invokes
+    /// `program` with `accounts` and `data`
+    fn allow_instruction(
+        &self,
+        _idx: usize,
+        _program: &AccountInfo,
+        _accounts: &[AccountInfo],
+        _data: &[u8],
+    ) -> bool {
+        true
+    }
+    /// returns false to reject forwarding the signature of `account`, a signer of
+    /// the outer transaction, into an inner instruction
+    fn allow_signer_forwarding(&self, _account: &AccountInfo) -> bool {
+        true
+    }
+}
+
+/// a policy which allows any program and instruction to be invoked
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct AllowAll;
+
+impl AnyIxPolicy for AllowAll {}
+
+/// a policy which only allows the listed programs to be invoked
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ProgramAllowlist<'a>(pub &'a [Pubkey]);
+
+impl AnyIxPolicy for ProgramAllowlist<'_> {
+    fn allow_program(&self, program_id: &Pubkey) -> bool {
+        self.0.contains(program_id)
+    }
+}
+
+/// a policy which allows any program except the listed ones to be invoked
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ProgramDenylist<'a>(pub &'a [Pubkey]);
+
+impl AnyIxPolicy for ProgramDenylist<'_> {
+    fn allow_program(&self, program_id: &Pubkey) -> bool {
+        !self.0.contains(program_id)
+    }
+}
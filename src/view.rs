@@ -0,0 +1,195 @@
+use solana_program::account_info::AccountInfo;
+use solana_program::instruction::{AccountMeta, Instruction};
+
+use crate::extension::ExtensionIter;
+use crate::{hash, AnyIx, AnyIxError, Extension, VERSIONED_TAG, VERSION_2, VERSION_3};
+
+/// a zero-copy view over a packed AnyIx payload, borrowing the instruction
+/// data directly from the input rather than copying it into vectors.
+///
+/// the payload is fully validated by `unpack`, so iterating over the
+/// instructions can not fail
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct AnyIxRef<'a> {
+    num_instructions: u16,
+    /// number of bytes used to encode each data size and account count
+    width: usize,
+    instruction_data_sizes: &'a [u8],
+    instruction_account_counts: &'a [u8],
+    instruction_datas: &'a [u8],
+    /// the extensions of a v3 payload, excluding the leading count
+    num_extensions: u8,
+    extensions: &'a [u8],
+}
+
+impl<'a> AnyIxRef<'a> {
+    /// decodes a v3 or v2 payload, or a legacy v1 payload
+    pub fn unpack(input: &'a [u8]) -> Result<Self, AnyIxError> {
+        if input.is_empty() {
+            return Err(AnyIxError::TruncatedHeader);
+        }
+        let mut version = 1;
+        let (num_instructions, width, data) = if input.len() > 1 && input[0] == VERSIONED_TAG {
+            version = input[1];
+            if version != VERSION_2 && version != VERSION_3 {
+                return Err(AnyIxError::UnsupportedVersion);
+            }
+            let (num_instructions, data) = split(&input[2..], 2)?;
+            (
+                u16::from_le_bytes([num_instructions[0], num_instructions[1]]),
+                2,
+                data,
+            )
+        } else {
+            (input[0] as u16, 1, &input[1..])
+        };
+        let (instruction_data_sizes, data) = split(data, num_instructions as usize * width)?;
+        let (instruction_account_counts, data) = split(data, num_instructions as usize * width)?;
+        let mut view = AnyIxRef {
+            num_instructions,
+            width,
+            instruction_data_sizes,
+            instruction_account_counts,
+            instruction_datas: &[],
+            num_extensions: 0,
+            extensions: &[],
+        };
+        let total_data_size: usize = (0..num_instructions as usize)
+            .map(|idx| view.data_size(idx) as usize)
+            .sum();
+        if total_data_size > data.len() {
+            return Err(AnyIxError::DataUnderflow);
+        }
+        let (instruction_datas, extensions) = data.split_at(total_data_size);
+        if version == VERSION_3 {
+            let extensions = ExtensionIter::new(extensions)?;
+            view.num_extensions = extensions.remaining;
+            view.extensions = extensions.data;
+        } else if !extensions.is_empty() {
+            return Err(AnyIxError::TrailingBytes);
+        }
+        view.instruction_datas = instruction_datas;
+        Ok(view)
+    }
+    /// the total number of individual instructions
+    pub fn num_instructions(&self) -> u16 {
+        self.num_instructions
+    }
+    /// returns an iterator over `(account_count, data)` for each instruction
+    pub fn iter(&self) -> AnyIxIter<'a> {
+        AnyIxIter {
+            view: *self,
+            idx: 0,
+            data_offset: 0,
+        }
+    }
+    /// returns an iterator which decodes each extension of the payload
+    pub fn extensions(&self) -> ExtensionIter<'a> {
+        ExtensionIter {
+            remaining: self.num_extensions,
+            data: self.extensions,
+        }
+    }
+    /// returns true if the payload carries any extensions
+    pub fn has_extensions(&self) -> bool {
+        self.num_extensions > 0
+    }
+    /// decodes the bundle back into its instructions, see `decode_instructions`
+    pub fn instructions(&self, accounts: &[AccountMeta]) -> Result<Vec<Instruction>, AnyIxError> {
+        let extensions = self.extensions().collect::<Result<Vec<_>, _>>()?;
+        let indices = extensions.iter().find_map(|extension| match extension {
+            Extension::AccountIndices(indices) => Some(&indices[..]),
+            _ => None,
+        });
+        crate::to_instructions(self.iter(), accounts, indices)
+    }
+    /// copies the view into an owned AnyIx, decoding its extensions
+    pub fn to_owned(&self) -> Result<AnyIx, AnyIxError> {
+        let (instruction_account_counts, instruction_datas): (Vec<u16>, Vec<Vec<u8>>) = self
+            .iter()
+            .map(|(account_count, data)| (account_count, data.to_vec()))
+            .unzip();
+        Ok(AnyIx {
+            num_instructions: self.num_instructions,
+            instruction_data_sizes: (0..self.num_instructions as usize)
+                .map(|idx| self.data_size(idx))
+                .collect(),
+            instruction_account_counts,
+            instruction_datas,
+            extensions: self.extensions().collect::<Result<_, _>>()?,
+        })
+    }
+    /// returns the sha256 hash of the canonical encoding of the bundle, matching
+    /// `AnyIx::hash` regardless of the version of the payload
+    pub fn hash(&self) -> Result<[u8; 32], AnyIxError> {
+        self.to_owned()?.hash()
+    }
+    /// returns the id of the bundle when executed with `accounts`, matching `AnyIx::id`
+    /// for the accounts built by the client
+    pub fn id(&self, accounts: &[AccountInfo<'_>]) -> Result<[u8; 32], AnyIxError> {
+        Ok(hash::bundle_id(
+            &self.hash()?,
+            &hash::hash_account_infos(accounts),
+        ))
+    }
+    pub(crate) fn data_size(&self, idx: usize) -> u16 {
+        read(self.instruction_data_sizes, self.width, idx)
+    }
+    fn account_count(&self, idx: usize) -> u16 {
+        read(self.instruction_account_counts, self.width, idx)
+    }
+}
+
+impl<'a> IntoIterator for &AnyIxRef<'a> {
+    type Item = (u16, &'a [u8]);
+    type IntoIter = AnyIxIter<'a>;
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+/// iterator over the `(account_count, data)` entries of an AnyIxRef
+#[derive(Clone, Debug)]
+pub struct AnyIxIter<'a> {
+    view: AnyIxRef<'a>,
+    idx: usize,
+    data_offset: usize,
+}
+
+impl<'a> Iterator for AnyIxIter<'a> {
+    type Item = (u16, &'a [u8]);
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.idx >= self.view.num_instructions as usize {
+            return None;
+        }
+        let data_size = self.view.data_size(self.idx) as usize;
+        let data = &self.view.instruction_datas[self.data_offset..self.data_offset + data_size];
+        let account_count = self.view.account_count(self.idx);
+        self.idx += 1;
+        self.data_offset += data_size;
+        Some((account_count, data))
+    }
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining = self.view.num_instructions as usize - self.idx;
+        (remaining, Some(remaining))
+    }
+}
+
+impl ExactSizeIterator for AnyIxIter<'_> {}
+
+// splits `count` bytes off the front of the input
+fn split(input: &[u8], count: usize) -> Result<(&[u8], &[u8]), AnyIxError> {
+    if input.len() < count {
+        return Err(AnyIxError::TruncatedHeader);
+    }
+    Ok(input.split_at(count))
+}
+
+// reads the `idx`'th value of `width` bytes, little endian
+fn read(values: &[u8], width: usize, idx: usize) -> u16 {
+    if width == 1 {
+        values[idx] as u16
+    } else {
+        u16::from_le_bytes([values[idx * 2], values[idx * 2 + 1]])
+    }
+}